@@ -1,7 +1,126 @@
 #![cfg_attr(test, allow(dead_code))]
 
 extern crate cupid;
+#[cfg(feature = "json")]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+use std::{env, process};
+
+#[cfg(feature = "json")]
+const USAGE: &str = "\
+Usage: dump-information [--format text|raw|json]
+
+Formats:
+    text    Decoded information in Rust debug format (default)
+    raw     Every CPUID leaf, in the format of `cpuid -1 -r`
+    json    Every decoded field and the raw leaf registers";
+
+#[cfg(not(feature = "json"))]
+const USAGE: &str = "\
+Usage: dump-information [--format text|raw]
+
+Formats:
+    text    Decoded information in Rust debug format (default)
+    raw     Every CPUID leaf, in the format of `cpuid -1 -r`";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Format {
+    Text,
+    Raw,
+    Json,
+}
+
+impl Format {
+    fn parse(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "raw" => Some(Format::Raw),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+fn parse_args<I>(args: I) -> Result<Format, String>
+    where I: IntoIterator<Item = String>
+{
+    let mut format = Format::Text;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let name = if arg == "--format" || arg == "-f" {
+            args.next().ok_or_else(|| format!("{} requires a value", arg))?
+        } else if let Some(name) = arg.strip_prefix("--format=") {
+            name.to_owned()
+        } else if arg == "--help" || arg == "-h" {
+            println!("{}", USAGE);
+            process::exit(0);
+        } else {
+            return Err(format!("unexpected argument `{}`", arg));
+        };
+
+        format = Format::parse(&name).ok_or_else(|| format!("unknown format `{}`", name))?;
+    }
+
+    Ok(format)
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+struct JsonOutput<'a> {
+    master: Option<cupid::Decoded<'a, cupid::Master>>,
+    raw: Option<cupid::RawDump>,
+}
+
+#[cfg(feature = "json")]
+fn print_json() -> Result<(), String> {
+    let master = cupid::master();
+    let output = JsonOutput {
+        master: master.as_ref().map(cupid::Decoded),
+        raw: cupid::raw_dump(),
+    };
+    let text = serde_json::to_string_pretty(&output).map_err(|e| e.to_string())?;
+    println!("{}", text);
+    Ok(())
+}
+
+#[cfg(not(feature = "json"))]
+fn print_json() -> Result<(), String> {
+    Err("the json format requires building cupid with `--features json`".to_owned())
+}
+
+fn run(format: Format) -> Result<(), String> {
+    match format {
+        Format::Text => println!("{:#?}", cupid::master()),
+        Format::Raw => {
+            let dump = cupid::raw_dump().ok_or("CPUID is not available on this platform")?;
+            print!("{}", dump.display_cpuid_r());
+        }
+        Format::Json => print_json()?,
+    }
+    Ok(())
+}
 
 fn main() {
-    println!("{:#?}", cupid::master());
+    let result = parse_args(env::args().skip(1)).and_then(run);
+
+    if let Err(e) = result {
+        eprintln!("dump-information: {}\n\n{}", e, USAGE);
+        process::exit(2);
+    }
+}
+
+#[test]
+fn format_arguments() {
+    let parse = |args: &[&str]| parse_args(args.iter().map(|&a| a.to_owned()));
+
+    assert_eq!(parse(&[]), Ok(Format::Text));
+    assert_eq!(parse(&["--format", "raw"]), Ok(Format::Raw));
+    assert_eq!(parse(&["--format=json"]), Ok(Format::Json));
+    assert_eq!(parse(&["-f", "text"]), Ok(Format::Text));
+    assert!(parse(&["--format", "yaml"]).is_err());
+    assert!(parse(&["--format"]).is_err());
+    assert!(parse(&["--verbose"]).is_err());
 }
@@ -0,0 +1,232 @@
+//! The flag names used by the Linux kernel in `/proc/cpuinfo`.
+
+use core::fmt;
+
+use super::{Feature, FeatureSet};
+
+impl Feature {
+    /// The name of the flag in `/proc/cpuinfo`, e.g. `lahf_lm` for
+    /// `Feature::LahfSahfIn64Bit`.
+    ///
+    /// Returns `None` for the few features the kernel does not list.
+    pub fn cpuinfo_name(self) -> Option<&'static str> {
+        let name = match self {
+            Feature::Sse3 => "pni",
+            Feature::Pclmulqdq => "pclmulqdq",
+            Feature::Dtes64 => "dtes64",
+            Feature::Monitor => "monitor",
+            Feature::DsCpl => "ds_cpl",
+            Feature::Vmx => "vmx",
+            Feature::Smx => "smx",
+            Feature::Eist => "est",
+            Feature::Tm2 => "tm2",
+            Feature::Ssse3 => "ssse3",
+            Feature::CnxtId => "cid",
+            Feature::Sdbg => "sdbg",
+            Feature::Fma => "fma",
+            Feature::Cmpxchg16b => "cx16",
+            Feature::XtprUpdateControl => "xtpr",
+            Feature::Pdcm => "pdcm",
+            Feature::Pcid => "pcid",
+            Feature::Dca => "dca",
+            Feature::Sse4_1 => "sse4_1",
+            Feature::Sse4_2 => "sse4_2",
+            Feature::X2apic => "x2apic",
+            Feature::Movbe => "movbe",
+            Feature::Popcnt => "popcnt",
+            Feature::TscDeadline => "tsc_deadline_timer",
+            Feature::Aesni => "aes",
+            Feature::Xsave => "xsave",
+            Feature::Osxsave => return None,
+            Feature::Avx => "avx",
+            Feature::F16c => "f16c",
+            Feature::Rdrand => "rdrand",
+
+            Feature::Fpu => "fpu",
+            Feature::Vme => "vme",
+            Feature::De => "de",
+            Feature::Pse => "pse",
+            Feature::Tsc => "tsc",
+            Feature::Msr => "msr",
+            Feature::Pae => "pae",
+            Feature::Mce => "mce",
+            Feature::Cx8 => "cx8",
+            Feature::Apic => "apic",
+            Feature::Sep => "sep",
+            Feature::Mtrr => "mtrr",
+            Feature::Pge => "pge",
+            Feature::Mca => "mca",
+            Feature::Cmov => "cmov",
+            Feature::Pat => "pat",
+            Feature::Pse36 => "pse36",
+            Feature::Psn => "pn",
+            Feature::Clfsh => "clflush",
+            Feature::Ds => "dts",
+            Feature::Acpi => "acpi",
+            Feature::Mmx => "mmx",
+            Feature::Fxsr => "fxsr",
+            Feature::Sse => "sse",
+            Feature::Sse2 => "sse2",
+            Feature::Ss => "ss",
+            Feature::Htt => "ht",
+            Feature::Tm => "tm",
+            Feature::Pbe => "pbe",
+
+            Feature::DigitalTemperatureSensor => "dtherm",
+            Feature::IntelTurboBoost => "ida",
+            Feature::Arat => "arat",
+            Feature::Pln => "pln",
+            Feature::Ecmd => return None,
+            Feature::Ptm => "pts",
+            Feature::Hwp => "hwp",
+            Feature::HwpNotification => "hwp_notify",
+            Feature::HwpActivityWindow => "hwp_act_window",
+            Feature::HwpEnergyPerformancePreference => "hwp_epp",
+            Feature::Hdc => return None,
+            Feature::HardwareCoordinationFeedback => "aperfmperf",
+            Feature::PerformanceEnergyBias => "epb",
+
+            Feature::Fsgsbase => "fsgsbase",
+            Feature::Ia32TscAdjustMsr => "tsc_adjust",
+            Feature::Bmi1 => "bmi1",
+            Feature::Hle => "hle",
+            Feature::Avx2 => "avx2",
+            Feature::Smep => "smep",
+            Feature::Bmi2 => "bmi2",
+            Feature::EnhancedRepMovsbStosb => "erms",
+            Feature::Invpcid => "invpcid",
+            Feature::Rtm => "rtm",
+            Feature::Pqm => "cqm",
+            Feature::DeprecatesFpuCsDs => return None,
+            Feature::Pqe => "rdt_a",
+            Feature::Avx512f => "avx512f",
+            Feature::Avx512dq => "avx512dq",
+            Feature::Rdseed => "rdseed",
+            Feature::Adx => "adx",
+            Feature::Smap => "smap",
+            Feature::Avx512ifma => "avx512ifma",
+            Feature::Clflushopt => "clflushopt",
+            Feature::Clwb => "clwb",
+            Feature::IntelProcessorTrace => "intel_pt",
+            Feature::Avx512pf => "avx512pf",
+            Feature::Avx512er => "avx512er",
+            Feature::Avx512cd => "avx512cd",
+            Feature::Sha => "sha_ni",
+            Feature::Avx512bw => "avx512bw",
+            Feature::Avx512vl => "avx512vl",
+
+            Feature::Prefetchwt1 => return None,
+            Feature::Avx512vbmi => "avx512vbmi",
+            Feature::Umip => "umip",
+            Feature::Pku => "pku",
+            Feature::Ospke => "ospke",
+            Feature::Avx512vbmi2 => "avx512_vbmi2",
+            Feature::Gfni => "gfni",
+            Feature::Vaes => "vaes",
+            Feature::Vpclmulqdq => "vpclmulqdq",
+            Feature::Avx512vnni => "avx512_vnni",
+            Feature::Avx512bitalg => "avx512_bitalg",
+            Feature::Avx512vpopcntdq => "avx512_vpopcntdq",
+            Feature::Rdpid => "rdpid",
+
+            Feature::Avx512_4vnniw => "avx512_4vnniw",
+            Feature::Avx512_4fmaps => "avx512_4fmaps",
+            Feature::Avx512Vp2intersect => "avx512_vp2intersect",
+            Feature::Avx512Fp16 => "avx512_fp16",
+
+            Feature::LahfSahfIn64Bit => "lahf_lm",
+            Feature::Lzcnt => "abm",
+            Feature::Sse4a => "sse4a",
+            Feature::Prefetchw => "3dnowprefetch",
+            Feature::Tbm => "tbm",
+            Feature::SyscallSysretIn64Bit => "syscall",
+            Feature::ExecuteDisable => "nx",
+            Feature::GigabytePages => "pdpe1gb",
+            Feature::RdtscpAndIa32TscAux => "rdtscp",
+            Feature::Intel64BitArchitecture => "lm",
+
+            Feature::InvariantTsc => "nonstop_tsc",
+        };
+        Some(name)
+    }
+
+    /// The feature with the given `/proc/cpuinfo` flag name.
+    pub fn from_cpuinfo_name(name: &str) -> Option<Feature> {
+        Feature::ALL.iter().cloned().find(|f| f.cpuinfo_name() == Some(name))
+    }
+}
+
+impl FeatureSet {
+    /// The features named in the `flags` line of `/proc/cpuinfo`.
+    ///
+    /// The `flags :` prefix is optional. Flags that do not correspond
+    /// to a feature, such as the kernel's synthetic `rep_good`, are
+    /// ignored.
+    pub fn from_cpuinfo_flags(flags: &str) -> FeatureSet {
+        let flags = match flags.find(':') {
+            Some(colon) => &flags[colon + 1..],
+            None => flags,
+        };
+        flags.split_whitespace().filter_map(Feature::from_cpuinfo_name).collect()
+    }
+
+    /// Displays the features as the `flags` of `/proc/cpuinfo` would,
+    /// skipping those the kernel does not list.
+    pub fn display_cpuinfo(&self) -> CpuinfoFlags {
+        CpuinfoFlags(*self)
+    }
+}
+
+/// Displays a `FeatureSet` using `/proc/cpuinfo` flag names.
+#[derive(Debug, Clone)]
+pub struct CpuinfoFlags(FeatureSet);
+
+impl fmt::Display for CpuinfoFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names = self.0.iter().filter_map(Feature::cpuinfo_name);
+        if let Some(name) = names.next() {
+            f.write_str(name)?;
+        }
+        for name in names {
+            write!(f, " {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn cpuinfo_names_round_trip() {
+    for &feature in Feature::ALL {
+        if let Some(name) = feature.cpuinfo_name() {
+            assert_eq!(Feature::from_cpuinfo_name(name), Some(feature));
+        }
+    }
+
+    let set = FeatureSet::from_cpuinfo_flags("flags\t\t: fpu pse36 rep_good aes lahf_lm erms");
+    let expected: FeatureSet = [Feature::Fpu, Feature::Pse36, Feature::Aesni,
+                                Feature::LahfSahfIn64Bit, Feature::EnhancedRepMovsbStosb]
+        .iter().cloned().collect();
+    assert_eq!(set, expected);
+    assert_eq!(set.display_cpuinfo().to_string(), "aes fpu pse36 erms lahf_lm");
+    assert_eq!(FeatureSet::from_cpuinfo_flags(&set.display_cpuinfo().to_string()), set);
+}
+
+#[test]
+fn matches_proc_cpuinfo() {
+    // The flags of the Core i7-4770 that `haswell_source` was taken from
+    const FLAGS: &str = "flags\t\t: fpu vme de pse tsc msr pae mce cx8 apic sep mtrr pge mca \
+        cmov pat pse36 clflush dts acpi mmx fxsr sse sse2 ss ht tm pbe syscall nx pdpe1gb rdtscp \
+        lm constant_tsc arch_perfmon pebs bts rep_good nopl xtopology nonstop_tsc cpuid \
+        aperfmperf pni pclmulqdq dtes64 monitor ds_cpl vmx smx est tm2 ssse3 sdbg fma cx16 xtpr \
+        pdcm pcid sse4_1 sse4_2 x2apic movbe popcnt tsc_deadline_timer aes xsave avx f16c rdrand \
+        lahf_lm abm cpuid_fault epb invpcid_single pti ssbd ibrs ibpb stibp tpr_shadow vnmi \
+        flexpriority ept vpid ept_ad fsgsbase tsc_adjust bmi1 avx2 smep bmi2 erms invpcid \
+        xsaveopt dtherm ida arat pln pts md_clear flush_l1d";
+
+    // Every feature the kernel lists is reported by CPUID, and the
+    // other way around
+    let detected: FeatureSet = ::Master::from_source(&::haswell_source).features().iter()
+        .filter(|f| f.cpuinfo_name().is_some())
+        .collect();
+    assert_eq!(FeatureSet::from_cpuinfo_flags(FLAGS), detected);
+}
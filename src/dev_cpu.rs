@@ -0,0 +1,122 @@
+//! Access to the CPUID instruction of any logical CPU through the
+//! Linux `cpuid` driver.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use super::{CpuidResult, CpuidSource, Error, Master};
+
+/// The CPUID instruction of one logical CPU, as exposed by the Linux
+/// `cpuid` driver at `/dev/cpu/N/cpuid`.
+///
+/// Unlike `Native`, the answers do not depend on which CPU the calling
+/// thread is scheduled on. Opening the device usually requires root
+/// (or `CAP_SYS_RAWIO`) and the `cpuid` kernel module.
+///
+/// A `CpuidSource` cannot fail, so a read error is returned as all-zero
+/// registers and kept until retrieved with `take_error`.
+#[derive(Debug)]
+pub struct DevCpuid {
+    cpu: usize,
+    file: File,
+    error: RefCell<Option<io::Error>>,
+}
+
+impl DevCpuid {
+    /// Opens the device of the given logical CPU.
+    pub fn open(cpu: usize) -> io::Result<DevCpuid> {
+        let path = format!("/dev/cpu/{}/cpuid", cpu);
+
+        let file = File::open(&path).map_err(|e| {
+            let reason = match e.kind() {
+                io::ErrorKind::NotFound if !Path::new("/dev/cpu/0/cpuid").exists() => {
+                    format!("{} does not exist; is the cpuid kernel module loaded?", path)
+                }
+                io::ErrorKind::NotFound => format!("CPU {} does not exist", cpu),
+                io::ErrorKind::PermissionDenied => {
+                    format!("permission denied opening {}; root or CAP_SYS_RAWIO is required", path)
+                }
+                _ => format!("unable to open {}: {}", path, e),
+            };
+            io::Error::new(e.kind(), reason)
+        })?;
+
+        Ok(DevCpuid { cpu, file, error: RefCell::new(None) })
+    }
+
+    /// The logical CPU this device belongs to.
+    pub fn cpu(&self) -> usize {
+        self.cpu
+    }
+
+    /// Executes CPUID on the CPU, reporting any error from the driver.
+    pub fn read(&self, leaf: u32, subleaf: u32) -> io::Result<CpuidResult> {
+        // The driver takes the leaf from the low and the subleaf from
+        // the high half of the file offset.
+        let offset = u64::from(subleaf) << 32 | u64::from(leaf);
+
+        let mut bytes = [0; 16];
+        self.file.read_exact_at(&mut bytes, offset)?;
+
+        let register = |i: usize| {
+            u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+        };
+        Ok(CpuidResult { eax: register(0), ebx: register(4), ecx: register(8), edx: register(12) })
+    }
+
+    /// The first read error since the last call, if any.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.borrow_mut().take()
+    }
+}
+
+impl CpuidSource for DevCpuid {
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuidResult {
+        self.read(leaf, subleaf).unwrap_or_else(|e| {
+            let mut error = self.error.borrow_mut();
+            if error.is_none() {
+                *error = Some(e);
+            }
+            CpuidResult::default()
+        })
+    }
+}
+
+/// The CPU information of the given logical CPU, read through
+/// `/dev/cpu/N/cpuid`.
+///
+/// Failures to open or read the device are reported as
+/// `Error::SourceError`.
+pub fn master_for_cpu(cpu: usize) -> Result<Master, Error> {
+    let source = DevCpuid::open(cpu)?;
+    let master = Master::from_source(&source);
+
+    match source.take_error() {
+        Some(e) => Err(e.into()),
+        None => Ok(master),
+    }
+}
+
+#[test]
+fn missing_cpu_is_an_error() {
+    match master_for_cpu(1 << 20) {
+        Err(Error::SourceError(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        other => panic!("expected a source error, got {:?}", other),
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[test]
+fn device_matches_instruction() {
+    // The driver is frequently not loaded, or we may not be root
+    let device = match DevCpuid::open(0) {
+        Ok(device) => device,
+        Err(_) => return,
+    };
+
+    assert_eq!(device.read(0, 0).unwrap(), ::query(0, 0));
+    assert!(master_for_cpu(0).is_ok());
+}
@@ -0,0 +1,595 @@
+//! Raw, undecoded CPUID register values.
+
+use alloc::vec::Vec;
+use core::{fmt, slice};
+#[cfg(feature = "std")]
+use std::error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{CpuidResult, CpuidSource};
+
+// Bounds the enumeration when a processor (or a recording) reports
+// nonsensical maximum leaves or never terminates a subleaf sequence.
+const MAX_LEAVES_PER_RANGE: u32 = 0x100;
+const MAX_SUBLEAVES: u32 = 0x40;
+
+const HYPERVISOR_BASE: u32 = 0x40000000;
+const EXTENDED_BASE: u32 = 0x80000000;
+
+/// How the valid subleaves of a leaf are discovered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Subleaves {
+    /// The leaf ignores ECX.
+    None,
+    /// Subleaves continue until the cache type in EAX[4:0] is null.
+    UntilNullCacheType,
+    /// Subleaves continue until the level type in ECX[15:8] is invalid.
+    UntilInvalidLevelType,
+    /// Subleaf 0 reports the maximum subleaf in EAX.
+    MaxInEax,
+    /// Subleaf 0 and 1 report the valid XSAVE state components.
+    XsaveComponents,
+    /// Subleaf 0 reports the valid subleaves as a bitmask in EBX.
+    MaskInEbx,
+    /// Subleaf 0 reports the valid subleaves as a bitmask in EDX.
+    MaskInEdx,
+    /// Subleaves continue until the sub-leaf type in EAX[3:0] is invalid.
+    UntilInvalidSgxType,
+}
+
+fn subleaves(leaf: u32) -> Subleaves {
+    match leaf {
+        0x00000004 | 0x8000001D => Subleaves::UntilNullCacheType,
+        0x0000000B | 0x0000001F | 0x80000026 => Subleaves::UntilInvalidLevelType,
+        0x00000007 | 0x00000014 | 0x00000017 | 0x00000018 |
+        0x0000001D | 0x00000020 => Subleaves::MaxInEax,
+        0x0000000D => Subleaves::XsaveComponents,
+        0x0000000F => Subleaves::MaskInEdx,
+        0x00000010 | 0x80000020 => Subleaves::MaskInEbx,
+        0x00000012 => Subleaves::UntilInvalidSgxType,
+        _ => Subleaves::None,
+    }
+}
+
+/// Whether the leaf returns different values depending on the subleaf.
+fn is_indexed(leaf: u32) -> bool {
+    subleaves(leaf) != Subleaves::None
+}
+
+/// The registers returned for one leaf and subleaf.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RawLeaf {
+    pub leaf: u32,
+    pub subleaf: u32,
+    pub registers: CpuidResult,
+}
+
+/// Every valid leaf and subleaf reported by a processor.
+///
+/// The standard, hypervisor and extended ranges are walked up to the
+/// maximum leaf each one reports, and indexed leaves are walked using
+/// the termination rule of that leaf. A dump is itself a
+/// `CpuidSource`, so it can be decoded with `Master::from_source`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawDump {
+    // Sorted by leaf, then subleaf
+    leaves: Vec<RawLeaf>,
+}
+
+impl RawDump {
+    /// Creates a dump without any leaves.
+    pub fn empty() -> RawDump {
+        RawDump::default()
+    }
+
+    /// Enumerates every valid leaf of the given source.
+    pub fn from_source<S>(source: &S) -> RawDump
+        where S: CpuidSource + ?Sized
+    {
+        let mut dump = RawDump::empty();
+
+        let max_value = dump.record_range(source, 0);
+
+        let hypervisor_present = max_value >= 1 && (source.cpuid(1, 0).ecx >> 31) & 1 != 0;
+        if hypervisor_present {
+            dump.record_range(source, HYPERVISOR_BASE);
+        }
+
+        dump.record_range(source, EXTENDED_BASE);
+
+        dump
+    }
+
+    fn record_range<S>(&mut self, source: &S, base: u32) -> u32
+        where S: CpuidSource + ?Sized
+    {
+        let max_value = source.cpuid(base, 0).eax;
+        if max_value < base || max_value - base >= MAX_LEAVES_PER_RANGE {
+            // Not implemented; record the answer so the absence is visible
+            self.record_leaf(source, base);
+            return max_value;
+        }
+
+        for leaf in base..max_value + 1 {
+            self.record_leaf(source, leaf);
+        }
+
+        max_value
+    }
+
+    fn record_leaf<S>(&mut self, source: &S, leaf: u32)
+        where S: CpuidSource + ?Sized
+    {
+        let first = source.cpuid(leaf, 0);
+        self.insert(leaf, 0, first);
+
+        let mut record = |subleaf| {
+            let registers = source.cpuid(leaf, subleaf);
+            self.insert(leaf, subleaf, registers);
+            registers
+        };
+
+        match subleaves(leaf) {
+            Subleaves::None => {}
+            Subleaves::UntilNullCacheType => {
+                if first.eax & 0x1F != 0 {
+                    for subleaf in 1..MAX_SUBLEAVES {
+                        if record(subleaf).eax & 0x1F == 0 { break }
+                    }
+                }
+            }
+            Subleaves::UntilInvalidLevelType => {
+                if (first.ecx >> 8) & 0xFF != 0 {
+                    for subleaf in 1..MAX_SUBLEAVES {
+                        if (record(subleaf).ecx >> 8) & 0xFF == 0 { break }
+                    }
+                }
+            }
+            Subleaves::MaxInEax => {
+                for subleaf in 1..first.eax.min(MAX_SUBLEAVES - 1) + 1 {
+                    record(subleaf);
+                }
+            }
+            Subleaves::XsaveComponents => {
+                let second = record(1);
+                let xcr0 = u64::from(first.eax) | u64::from(first.edx) << 32;
+                let xss = u64::from(second.ecx) | u64::from(second.edx) << 32;
+                let components = xcr0 | xss;
+                for subleaf in 2..64 {
+                    if (components >> subleaf) & 1 != 0 {
+                        record(subleaf);
+                    }
+                }
+            }
+            Subleaves::MaskInEbx => {
+                for subleaf in 1..32 {
+                    if (first.ebx >> subleaf) & 1 != 0 {
+                        record(subleaf);
+                    }
+                }
+            }
+            Subleaves::MaskInEdx => {
+                for subleaf in 1..32 {
+                    if (first.edx >> subleaf) & 1 != 0 {
+                        record(subleaf);
+                    }
+                }
+            }
+            Subleaves::UntilInvalidSgxType => {
+                record(1);
+                for subleaf in 2..MAX_SUBLEAVES {
+                    if record(subleaf).eax & 0xF == 0 { break }
+                }
+            }
+        }
+    }
+
+    /// Adds or replaces the registers of a leaf and subleaf.
+    pub fn insert(&mut self, leaf: u32, subleaf: u32, registers: CpuidResult) {
+        let raw = RawLeaf { leaf, subleaf, registers };
+        match self.leaves.binary_search_by_key(&(leaf, subleaf), |l| (l.leaf, l.subleaf)) {
+            Ok(idx) => self.leaves[idx] = raw,
+            Err(idx) => self.leaves.insert(idx, raw),
+        }
+    }
+
+    /// The registers recorded for the leaf and subleaf, if any.
+    pub fn get(&self, leaf: u32, subleaf: u32) -> Option<CpuidResult> {
+        self.leaves
+            .binary_search_by_key(&(leaf, subleaf), |l| (l.leaf, l.subleaf))
+            .ok()
+            .map(|idx| self.leaves[idx].registers)
+    }
+
+    /// All recorded leaves, ordered by leaf and then subleaf.
+    pub fn leaves(&self) -> &[RawLeaf] {
+        &self.leaves
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Parses the raw output of the `cpuid` utility (`cpuid -r`).
+    ///
+    /// One dump is returned per `CPU` section, in the order they
+    /// appear. Output of `cpuid -1 -r` produces a single dump.
+    pub fn parse_cpuid_r(text: &str) -> Result<Vec<RawDump>, ParseError> {
+        let mut dumps = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with("CPU") && line.ends_with(':') {
+                dumps.push(RawDump::empty());
+                continue;
+            }
+
+            let (leaf, subleaf, registers) = parse_cpuid_r_line(line).ok_or(ParseError {
+                line: line_number,
+                reason: "expected `0xLEAF 0xSUBLEAF: eax=0x.. ebx=0x.. ecx=0x.. edx=0x..`",
+            })?;
+
+            if dumps.is_empty() {
+                dumps.push(RawDump::empty());
+            }
+            let dump = dumps.last_mut().expect("a dump was just added");
+            dump.insert(leaf, subleaf, registers);
+        }
+
+        Ok(dumps)
+    }
+
+    /// Parses a CPUID dump in the format of the InstLatX64 collection,
+    /// as written by AIDA64.
+    ///
+    /// One dump is returned per logical CPU section. Lines outside of
+    /// `CPUID` records, such as the program and motherboard headers,
+    /// are ignored. Subleaves are taken from the `[SL nn]` annotation
+    /// when present, otherwise repeated records of a leaf are numbered
+    /// consecutively.
+    pub fn parse_instlatx64(text: &str) -> Result<Vec<RawDump>, ParseError> {
+        let mut dumps = Vec::new();
+        let mut last: Option<(u32, u32)> = None;
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.trim();
+
+            let new_section =
+                (line.starts_with("------[") && line.contains("CPU #")) ||
+                line.starts_with("CPU#");
+            if new_section {
+                dumps.push(RawDump::empty());
+                last = None;
+                continue;
+            }
+
+            if !line.starts_with("CPUID ") {
+                continue;
+            }
+
+            let (leaf, subleaf, registers) = parse_instlatx64_line(line).ok_or(ParseError {
+                line: line_number,
+                reason: "expected `CPUID LEAF: EAX-EBX-ECX-EDX`",
+            })?;
+
+            let subleaf = subleaf.unwrap_or_else(|| {
+                match last {
+                    Some((last_leaf, last_subleaf)) if last_leaf == leaf => last_subleaf + 1,
+                    _ => 0,
+                }
+            });
+            last = Some((leaf, subleaf));
+
+            if dumps.is_empty() {
+                dumps.push(RawDump::empty());
+            }
+            let dump = dumps.last_mut().expect("a dump was just added");
+            dump.insert(leaf, subleaf, registers);
+        }
+
+        Ok(dumps)
+    }
+
+    /// Formats this dump as the output of `cpuid -1 -r`.
+    pub fn display_cpuid_r(&self) -> CpuidR<'_> {
+        CpuidR::new(slice::from_ref(self))
+    }
+}
+
+fn parse_cpuid_r_line(line: &str) -> Option<(u32, u32, CpuidResult)> {
+    let mut parts = line.split_whitespace();
+
+    let leaf = parse_hex(parts.next()?)?;
+    let subleaf = parse_hex(parts.next()?.trim_end_matches(':'))?;
+
+    let mut registers = CpuidResult::default();
+    let mut seen = 0;
+    for part in parts {
+        let mut pieces = part.splitn(2, '=');
+        let name = pieces.next()?;
+        let value = parse_hex(pieces.next()?)?;
+        match name {
+            "eax" => registers.eax = value,
+            "ebx" => registers.ebx = value,
+            "ecx" => registers.ecx = value,
+            "edx" => registers.edx = value,
+            _ => return None,
+        }
+        seen += 1;
+    }
+
+    if seen == 4 {
+        Some((leaf, subleaf, registers))
+    } else {
+        None
+    }
+}
+
+fn parse_instlatx64_line(line: &str) -> Option<(u32, Option<u32>, CpuidResult)> {
+    let line = line.trim_start_matches("CPUID").trim_start();
+
+    let colon = line.find(':')?;
+    let leaf = parse_hex(&line[..colon])?;
+    let rest = line[colon + 1..].trim_start();
+
+    let values = rest.split_whitespace().next()?;
+    let mut values = values.split('-').map(parse_hex);
+    let registers = CpuidResult {
+        eax: values.next()??,
+        ebx: values.next()??,
+        ecx: values.next()??,
+        edx: values.next()??,
+    };
+    if values.next().is_some() {
+        return None;
+    }
+
+    let subleaf = match rest.find("[SL ") {
+        Some(start) => {
+            let annotation = &rest[start + 4..];
+            let end = annotation.find(']')?;
+            Some(parse_hex(annotation[..end].trim())?)
+        }
+        None => None,
+    };
+
+    Some((leaf, subleaf, registers))
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Formats dumps as the raw output of the `cpuid` utility.
+///
+/// A single dump is written as `cpuid -1 -r` does; several dumps are
+/// written as one `CPU n` section each, like `cpuid -r`.
+#[derive(Debug, Copy, Clone)]
+pub struct CpuidR<'a> {
+    dumps: &'a [RawDump],
+}
+
+impl<'a> CpuidR<'a> {
+    pub fn new(dumps: &'a [RawDump]) -> CpuidR<'a> {
+        CpuidR { dumps }
+    }
+}
+
+impl<'a> fmt::Display for CpuidR<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (cpu, dump) in self.dumps.iter().enumerate() {
+            if self.dumps.len() == 1 {
+                writeln!(f, "CPU:")?;
+            } else {
+                writeln!(f, "CPU {}:", cpu)?;
+            }
+
+            for l in dump.leaves() {
+                let r = l.registers;
+                writeln!(f, "   0x{:08x} 0x{:02x}: eax=0x{:08x} ebx=0x{:08x} ecx=0x{:08x} edx=0x{:08x}",
+                         l.leaf, l.subleaf, r.eax, r.ebx, r.ecx, r.edx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a textual CPUID dump cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    reason: &'static str,
+}
+
+impl ParseError {
+    /// The 1-based line number of the offending input.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for ParseError {}
+
+impl CpuidSource for RawDump {
+    /// Returns the recorded registers, or all zeros when the leaf was
+    /// not recorded. The subleaf is ignored for leaves that are not
+    /// indexed.
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuidResult {
+        let subleaf = if is_indexed(leaf) { subleaf } else { 0 };
+        self.get(leaf, subleaf).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+fn regs(eax: u32, ebx: u32, ecx: u32, edx: u32) -> CpuidResult {
+    CpuidResult { eax, ebx, ecx, edx }
+}
+
+#[test]
+fn dump_round_trips_through_master() {
+    let dump = RawDump::from_source(&::haswell_source);
+
+    let direct = ::Master::from_source(&::haswell_source);
+    let replayed = ::Master::from_source(&dump);
+    assert_eq!(format!("{:?}", direct), format!("{:?}", replayed));
+
+    assert_eq!(dump.get(0x80000008, 0), Some(::haswell_source(0x80000008, 0)));
+    assert_eq!(dump.get(0x0000000C, 0), Some(CpuidResult::default()));
+    assert_eq!(dump.get(0x80000009, 0), None);
+    assert_eq!(dump.get(0x40000000, 0), None);
+}
+
+#[test]
+fn cpuid_r_round_trips() {
+    let text = "\
+CPU 0:
+   0x00000000 0x00: eax=0x0000000d ebx=0x756e6547 ecx=0x6c65746e edx=0x49656e69
+   0x00000001 0x00: eax=0x000306c3 ebx=0x00100800 ecx=0x7ffafbff edx=0xbfebfbff
+   0x00000007 0x00: eax=0x00000000 ebx=0x000027ab ecx=0x00000000 edx=0x00000000
+   0x80000000 0x00: eax=0x80000001 ebx=0x00000000 ecx=0x00000000 edx=0x00000000
+   0x80000001 0x00: eax=0x00000000 ebx=0x00000000 ecx=0x00000021 edx=0x2c100800
+CPU 1:
+   0x00000000 0x00: eax=0x0000000d ebx=0x756e6547 ecx=0x6c65746e edx=0x49656e69
+   0x00000001 0x00: eax=0x000306c3 ebx=0x02100800 ecx=0x7ffafbff edx=0xbfebfbff
+";
+    let dumps = RawDump::parse_cpuid_r(text).unwrap();
+    assert_eq!(dumps.len(), 2);
+    assert_eq!(dumps[1].get(1, 0).unwrap().ebx, 0x02100800);
+
+    let master = ::Master::from_source(&dumps[0]);
+    assert!(master.avx2());
+    assert!(master.lzcnt());
+    assert_eq!(master.version_information().unwrap().model_id(), 0x3C);
+
+    assert_eq!(CpuidR::new(&dumps).to_string(), text);
+
+    let single = dumps[0].display_cpuid_r().to_string();
+    assert!(single.starts_with("CPU:\n"));
+    assert_eq!(RawDump::parse_cpuid_r(&single).unwrap(), &dumps[..1]);
+}
+
+#[test]
+fn cpuid_r_reports_malformed_lines() {
+    let text = "CPU:\n   0x00000000 0x00: eax=0x0000000d ebx=0x756e6547\n";
+    assert_eq!(RawDump::parse_cpuid_r(text).unwrap_err().line(), 2);
+}
+
+#[test]
+fn instlatx64_sections_and_subleaves() {
+    let text = "\
+------[ Versions ]------
+
+Program Version : AIDA64 Extreme v5.00.3300
+Windows Version : Microsoft Windows 8.1 Pro 6.3.9600 (64-bit)
+
+------[ Logical CPU #0 ]------
+
+CPUID 00000000: 0000000D-756E6547-6C65746E-49656E69 [GenuineIntel]
+CPUID 00000001: 000306C3-00100800-7FFAFBFF-BFEBFBFF
+CPUID 00000004: 1C004121-01C0003F-0000003F-00000000 [SL 00]
+CPUID 00000004: 1C004122-01C0003F-0000003F-00000000 [SL 01]
+CPUID 00000007: 00000000-000027AB-00000000-00000000
+CPUID 0000000B: 00000001-00000002-00000100-00000000
+CPUID 0000000B: 00000004-00000008-00000201-00000000
+CPUID 80000000: 80000008-00000000-00000000-00000000
+CPUID 80000001: 00000000-00000000-00000021-2C100800
+CPUID 80000002: 65746E49-2952286C-726F4320-4D542865 [Intel(R) Core(TM) i7-4770 CPU @ 3.40GHz]
+
+------[ Logical CPU #1 ]------
+
+CPUID 00000000: 0000000D-756E6547-6C65746E-49656E69 [GenuineIntel]
+CPUID 00000001: 000306C3-01100800-7FFAFBFF-BFEBFBFF
+";
+    let dumps = RawDump::parse_instlatx64(text).unwrap();
+    assert_eq!(dumps.len(), 2);
+
+    assert_eq!(dumps[0].get(0x4, 1).unwrap().eax, 0x1C004122);
+    assert_eq!(dumps[0].get(0xB, 1).unwrap().ecx, 0x201);
+    assert_eq!(dumps[1].get(0x1, 0).unwrap().ebx, 0x01100800);
+
+    let master = ::Master::from_source(&dumps[0]);
+    assert!(master.avx2());
+    assert!(master.lzcnt());
+    assert_eq!(master.version_information().unwrap().model_id(), 0x3C);
+}
+
+#[test]
+fn instlatx64_reports_malformed_records() {
+    let text = "CPUID 00000000: 0000000D-756E6547-6C65746E\n";
+    assert_eq!(RawDump::parse_instlatx64(text).unwrap_err().line(), 1);
+}
+
+#[test]
+fn master_writes_decoded_leaves() {
+    let master = ::Master::from_source(&::haswell_source);
+    let dump = master.raw_dump();
+
+    assert_eq!(dump.get(0x80000003, 0), Some(::haswell_source(0x80000003, 0)));
+    assert_eq!(dump.get(0x80000005, 0), None);
+
+    let text = dump.display_cpuid_r().to_string();
+    let reparsed = RawDump::parse_cpuid_r(&text).unwrap();
+    assert_eq!(format!("{:?}", ::Master::from_source(&reparsed[0])), format!("{:?}", master));
+}
+
+#[test]
+fn dump_follows_subleaf_termination_rules() {
+    let source = |leaf, subleaf| {
+        match (leaf, subleaf) {
+            (0x00000000, _) => regs(0x0000000D, 0, 0, 0),
+            // Three caches, then a null cache type
+            (0x00000004, 0..=2) => regs(0x1C004121 + subleaf, 0, 0, 0),
+            // Two valid subleaves
+            (0x00000007, 0) => regs(1, 0, 0, 0),
+            (0x00000007, 1) => regs(0x20, 0, 0, 0),
+            // SMT and core levels
+            (0x0000000B, 0) => regs(1, 2, 0x100, 0),
+            (0x0000000B, 1) => regs(4, 8, 0x201, 0),
+            (0x0000000B, _) => regs(0, 0, subleaf, 0),
+            // x87, SSE, AVX in XCR0; PT in XSS
+            (0x0000000D, 0) => regs(0x7, 0, 0, 0),
+            (0x0000000D, 1) => regs(0xF, 0, 0x100, 0),
+            (0x0000000D, _) => regs(subleaf, 0, 0, 0),
+            (0x80000000, _) => regs(0x80000001, 0, 0, 0),
+            _ => regs(0, 0, 0, 0),
+        }
+    };
+    let dump = RawDump::from_source(&source);
+
+    let subleaves_of = |leaf| {
+        dump.leaves().iter().filter(|l| l.leaf == leaf).map(|l| l.subleaf).collect::<Vec<_>>()
+    };
+
+    assert_eq!(subleaves_of(0x4), [0, 1, 2, 3]);
+    assert_eq!(subleaves_of(0x7), [0, 1]);
+    assert_eq!(subleaves_of(0xB), [0, 1, 2]);
+    assert_eq!(subleaves_of(0xD), [0, 1, 2, 8]);
+    assert_eq!(subleaves_of(0x1), [0]);
+    assert_eq!(dump.len(), 0xE + 9 + 2);
+
+    assert_eq!(dump.cpuid(0x7, 1).eax, 0x20);
+    assert_eq!(dump.cpuid(0x1, 5), dump.cpuid(0x1, 0));
+    assert_eq!(dump.cpuid(0x4, 9), CpuidResult::default());
+}
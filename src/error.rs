@@ -0,0 +1,65 @@
+//! Why CPU information is unavailable.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{error, io};
+
+use super::LeafGroup;
+
+/// The reason some CPU information could not be provided.
+///
+/// More variants may be added, and `SourceError` only exists with the
+/// `std` feature, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The target is not an x86 or x86_64 processor.
+    UnsupportedArchitecture,
+    /// The processor does not implement the CPUID instruction.
+    CpuidUnavailable,
+    /// The leaf is beyond the maximum leaf the processor reports.
+    LeafNotSupported { leaf: u32, max: u32 },
+    /// The leaves of the group were not selected when building the
+    /// `Master`; see `Master::fetch`.
+    NotQueried(LeafGroup),
+    /// The source of the registers failed, e.g. the `cpuid` driver.
+    #[cfg(feature = "std")]
+    SourceError(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnsupportedArchitecture => {
+                f.write_str("CPUID is only available on x86 and x86_64")
+            }
+            Error::CpuidUnavailable => {
+                f.write_str("the processor does not implement CPUID")
+            }
+            Error::LeafNotSupported { leaf, max } => {
+                write!(f, "leaf {:#010x} is not supported; the maximum leaf is {:#010x}",
+                       leaf, max)
+            }
+            Error::NotQueried(group) => write!(f, "{:?} was not queried", group),
+            #[cfg(feature = "std")]
+            Error::SourceError(ref e) => write!(f, "unable to execute CPUID: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::SourceError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::SourceError(e)
+    }
+}
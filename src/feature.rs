@@ -0,0 +1,648 @@
+//! Every flag decoded by `Master` as a value that can be inspected and
+//! looked up at runtime.
+
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::{BitAnd, BitOr, BitXor, Sub};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{CpuidResult, ExtendedProcessorSignature, Master, StructuredExtendedInformation,
+            ThermalPowerManagementInformation, TimeStampCounter, VersionInformation};
+
+/// One of the four registers written by CPUID.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Register {
+    Eax,
+    Ebx,
+    Ecx,
+    Edx,
+}
+
+impl Register {
+    /// The value of this register in the given result.
+    pub fn of(self, registers: CpuidResult) -> u32 {
+        match self {
+            Register::Eax => registers.eax,
+            Register::Ebx => registers.ebx,
+            Register::Ecx => registers.ecx,
+            Register::Edx => registers.edx,
+        }
+    }
+}
+
+// Every flag is listed once, by leaf and register. This generates the
+// flag methods of the leaf structs and of `Master` along with `Feature`.
+macro_rules! features {
+    ($($leaf:expr, $subleaf:expr, $kind:ident, $item:ident => {
+        $($register:ident => {
+            $($bit:expr => $variant:ident, $method:ident;)+
+        })+
+    })+) => {
+        /// A processor feature flag reported by CPUID.
+        ///
+        /// The name of each feature is the name of the corresponding
+        /// method on `Master`.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub enum Feature {
+            $($($($variant,)+)+)+
+        }
+
+        impl Feature {
+            /// Every feature, in leaf, register and bit order.
+            pub const ALL: &'static [Feature] = &[$($($(Feature::$variant,)+)+)+];
+
+            /// The leaf (EAX) reporting the feature.
+            pub fn leaf(self) -> u32 {
+                match self {
+                    $($($(Feature::$variant => $leaf,)+)+)+
+                }
+            }
+
+            /// The subleaf (ECX) reporting the feature.
+            pub fn subleaf(self) -> u32 {
+                match self {
+                    $($($(Feature::$variant => $subleaf,)+)+)+
+                }
+            }
+
+            /// The register reporting the feature.
+            pub fn register(self) -> Register {
+                match self {
+                    $($($(Feature::$variant => Register::$register,)+)+)+
+                }
+            }
+
+            /// The bit of the register reporting the feature.
+            pub fn bit(self) -> u8 {
+                match self {
+                    $($($(Feature::$variant => $bit,)+)+)+
+                }
+            }
+
+            /// The name of the method on `Master`, e.g. `sse4_2`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($($(Feature::$variant => stringify!($method),)+)+)+
+                }
+            }
+        }
+
+        $(impl $kind {
+            // The features of the leaf, in the order they are decoded.
+            pub(crate) const FEATURES: &'static [Feature] = &[$($(Feature::$variant,)+)+];
+
+            $($(pub fn $method(self) -> bool {
+                Feature::$variant.is_set_in(self.registers())
+            })+)+
+        })+
+
+        impl Master {
+            $($($(pub fn $method(&self) -> bool {
+                self.$item.map(|i| i.$method()).unwrap_or(false)
+            })+)+)+
+        }
+
+        #[cfg(test)]
+        fn by_method(master: &Master, feature: Feature) -> bool {
+            match feature {
+                $($($(Feature::$variant => master.$method(),)+)+)+
+            }
+        }
+    }
+}
+
+features! {
+    0x00000001, 0, VersionInformation, version_information => {
+        Ecx => {
+             0 => Sse3, sse3;
+             1 => Pclmulqdq, pclmulqdq;
+             2 => Dtes64, dtes64;
+             3 => Monitor, monitor;
+             4 => DsCpl, ds_cpl;
+             5 => Vmx, vmx;
+             6 => Smx, smx;
+             7 => Eist, eist;
+             8 => Tm2, tm2;
+             9 => Ssse3, ssse3;
+            10 => CnxtId, cnxt_id;
+            11 => Sdbg, sdbg;
+            12 => Fma, fma;
+            13 => Cmpxchg16b, cmpxchg16b;
+            14 => XtprUpdateControl, xtpr_update_control;
+            15 => Pdcm, pdcm;
+            // 16 - reserved
+            17 => Pcid, pcid;
+            18 => Dca, dca;
+            19 => Sse4_1, sse4_1;
+            20 => Sse4_2, sse4_2;
+            21 => X2apic, x2apic;
+            22 => Movbe, movbe;
+            23 => Popcnt, popcnt;
+            24 => TscDeadline, tsc_deadline;
+            25 => Aesni, aesni;
+            26 => Xsave, xsave;
+            27 => Osxsave, osxsave;
+            28 => Avx, avx;
+            29 => F16c, f16c;
+            30 => Rdrand, rdrand;
+            // 31 - unused
+        }
+        Edx => {
+             0 => Fpu, fpu;
+             1 => Vme, vme;
+             2 => De, de;
+             3 => Pse, pse;
+             4 => Tsc, tsc;
+             5 => Msr, msr;
+             6 => Pae, pae;
+             7 => Mce, mce;
+             8 => Cx8, cx8;
+             9 => Apic, apic;
+            // 10 - reserved
+            11 => Sep, sep;
+            12 => Mtrr, mtrr;
+            13 => Pge, pge;
+            14 => Mca, mca;
+            15 => Cmov, cmov;
+            16 => Pat, pat;
+            17 => Pse36, pse_36;
+            18 => Psn, psn;
+            19 => Clfsh, clfsh;
+            // 20 - reserved
+            21 => Ds, ds;
+            22 => Acpi, acpi;
+            23 => Mmx, mmx;
+            24 => Fxsr, fxsr;
+            25 => Sse, sse;
+            26 => Sse2, sse2;
+            27 => Ss, ss;
+            28 => Htt, htt;
+            29 => Tm, tm;
+            // 30 - reserved
+            31 => Pbe, pbe;
+        }
+    }
+    0x00000006, 0, ThermalPowerManagementInformation, thermal_power_management_information => {
+        Eax => {
+             0 => DigitalTemperatureSensor, digital_temperature_sensor;
+             1 => IntelTurboBoost, intel_turbo_boost;
+             2 => Arat, arat;
+            // 3 - reserved
+             4 => Pln, pln;
+             5 => Ecmd, ecmd;
+             6 => Ptm, ptm;
+             7 => Hwp, hwp;
+             8 => HwpNotification, hwp_notification;
+             9 => HwpActivityWindow, hwp_activity_window;
+            10 => HwpEnergyPerformancePreference, hwp_energy_performance_preference;
+            // 12 - reserved
+            13 => Hdc, hdc;
+        }
+        Ecx => {
+             0 => HardwareCoordinationFeedback, hardware_coordination_feedback;
+            // 1-2 - reserved
+             3 => PerformanceEnergyBias, performance_energy_bias;
+        }
+    }
+    0x00000007, 0, StructuredExtendedInformation, structured_extended_information => {
+        Ebx => {
+             0 => Fsgsbase, fsgsbase;
+             1 => Ia32TscAdjustMsr, ia32_tsc_adjust_msr;
+            // 2 - reserved
+             3 => Bmi1, bmi1;
+             4 => Hle, hle;
+             5 => Avx2, avx2;
+            // 6 - reserved
+             7 => Smep, smep;
+             8 => Bmi2, bmi2;
+             9 => EnhancedRepMovsbStosb, enhanced_rep_movsb_stosb;
+            10 => Invpcid, invpcid;
+            11 => Rtm, rtm;
+            12 => Pqm, pqm;
+            13 => DeprecatesFpuCsDs, deprecates_fpu_cs_ds;
+            // 14 - reserved
+            15 => Pqe, pqe;
+            16 => Avx512f, avx512f;
+            17 => Avx512dq, avx512dq;
+            18 => Rdseed, rdseed;
+            19 => Adx, adx;
+            20 => Smap, smap;
+            21 => Avx512ifma, avx512ifma;
+            // 22 - reserved
+            23 => Clflushopt, clflushopt;
+            24 => Clwb, clwb;
+            25 => IntelProcessorTrace, intel_processor_trace;
+            26 => Avx512pf, avx512pf;
+            27 => Avx512er, avx512er;
+            28 => Avx512cd, avx512cd;
+            29 => Sha, sha;
+            30 => Avx512bw, avx512bw;
+            31 => Avx512vl, avx512vl;
+        }
+        Ecx => {
+             0 => Prefetchwt1, prefetchwt1;
+             1 => Avx512vbmi, avx512vbmi;
+             2 => Umip, umip;
+             3 => Pku, pku;
+             4 => Ospke, ospke;
+            // 5 - reserved
+             6 => Avx512vbmi2, avx512vbmi2;
+            // 7 - reserved
+             8 => Gfni, gfni;
+             9 => Vaes, vaes;
+            10 => Vpclmulqdq, vpclmulqdq;
+            11 => Avx512vnni, avx512vnni;
+            12 => Avx512bitalg, avx512bitalg;
+            // 13 - reserved
+            14 => Avx512vpopcntdq, avx512vpopcntdq;
+            // 15-21 - reserved
+            22 => Rdpid, rdpid;
+            // 23-31 - reserved
+        }
+        Edx => {
+            // 0-1 - reserved
+             2 => Avx512_4vnniw, avx512_4vnniw;
+             3 => Avx512_4fmaps, avx512_4fmaps;
+            // 4-7 - reserved
+             8 => Avx512Vp2intersect, avx512_vp2intersect;
+            // 9-22 - reserved
+            23 => Avx512Fp16, avx512_fp16;
+            // 24-31 - reserved
+        }
+    }
+    0x80000001, 0, ExtendedProcessorSignature, extended_processor_signature => {
+        Ecx => {
+             0 => LahfSahfIn64Bit, lahf_sahf_in_64_bit;
+            // 1-4 reserved
+             5 => Lzcnt, lzcnt;
+             6 => Sse4a, sse4a;
+            // 7 reserved
+             8 => Prefetchw, prefetchw;
+            // 9-20 reserved
+            21 => Tbm, tbm;
+            // 22-31 reserved
+        }
+        Edx => {
+            // 0-10 reserved
+            11 => SyscallSysretIn64Bit, syscall_sysret_in_64_bit;
+            // 12-19 reserved
+            20 => ExecuteDisable, execute_disable;
+            // 21-25 reserved
+            26 => GigabytePages, gigabyte_pages;
+            27 => RdtscpAndIa32TscAux, rdtscp_and_ia32_tsc_aux;
+            // 28 reserved
+            29 => Intel64BitArchitecture, intel_64_bit_architecture;
+            // 30-31 reserved
+        }
+    }
+    0x80000007, 0, TimeStampCounter, time_stamp_counter => {
+        Edx => {
+            // 0-7 - reserved
+             8 => InvariantTsc, invariant_tsc;
+            // 9-31 - reserved
+        }
+    }
+}
+
+impl Feature {
+    /// The feature with the given `Master` method name, such as `avx2`.
+    pub fn from_name(name: &str) -> Option<Feature> {
+        Feature::ALL.iter().cloned().find(|f| f.name() == name)
+    }
+
+    /// Whether the feature is set in the registers of its leaf.
+    pub fn is_set_in(self, registers: CpuidResult) -> bool {
+        (self.register().of(registers) >> self.bit()) & 1 != 0
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+const WORDS: usize = 4;
+
+// Every feature needs a bit in a `FeatureSet`.
+const _: () = assert!(Feature::ALL.len() <= WORDS * 64);
+
+/// A set of features, stored as one bit per feature.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FeatureSet {
+    words: [u64; WORDS],
+}
+
+impl FeatureSet {
+    /// The empty set
+    pub fn new() -> FeatureSet {
+        FeatureSet::default()
+    }
+
+    /// The set of every feature known to this crate
+    pub fn all() -> FeatureSet {
+        Feature::ALL.iter().cloned().collect()
+    }
+
+    fn position(feature: Feature) -> (usize, u64) {
+        let index = feature as usize;
+        (index / 64, 1 << (index % 64))
+    }
+
+    /// Adds a feature, returning whether it was not already present.
+    pub fn insert(&mut self, feature: Feature) -> bool {
+        let (word, mask) = FeatureSet::position(feature);
+        let added = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        added
+    }
+
+    /// Removes a feature, returning whether it was present.
+    pub fn remove(&mut self, feature: Feature) -> bool {
+        let (word, mask) = FeatureSet::position(feature);
+        let removed = self.words[word] & mask != 0;
+        self.words[word] &= !mask;
+        removed
+    }
+
+    pub fn contains(&self, feature: Feature) -> bool {
+        let (word, mask) = FeatureSet::position(feature);
+        self.words[word] & mask != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    fn combine<F>(&self, other: &FeatureSet, op: F) -> FeatureSet
+        where F: Fn(u64, u64) -> u64
+    {
+        let mut words = [0; WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = op(self.words[i], other.words[i]);
+        }
+        FeatureSet { words }
+    }
+
+    /// The features in either set
+    pub fn union(&self, other: &FeatureSet) -> FeatureSet {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /// The features in both sets
+    pub fn intersection(&self, other: &FeatureSet) -> FeatureSet {
+        self.combine(other, |a, b| a & b)
+    }
+
+    /// The features in this set but not in `other`
+    pub fn difference(&self, other: &FeatureSet) -> FeatureSet {
+        self.combine(other, |a, b| a & !b)
+    }
+
+    /// The features in exactly one of the sets
+    pub fn symmetric_difference(&self, other: &FeatureSet) -> FeatureSet {
+        self.combine(other, |a, b| a ^ b)
+    }
+
+    pub fn is_subset(&self, other: &FeatureSet) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    pub fn is_superset(&self, other: &FeatureSet) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &FeatureSet) -> bool {
+        self.intersection(other).is_empty()
+    }
+
+    /// The features in the set, in the order of `Feature::ALL`.
+    pub fn iter(&self) -> Features {
+        Features { set: *self, next: 0 }
+    }
+}
+
+/// The features of a `FeatureSet`
+#[derive(Debug, Clone)]
+pub struct Features {
+    set: FeatureSet,
+    next: usize,
+}
+
+impl Iterator for Features {
+    type Item = Feature;
+
+    fn next(&mut self) -> Option<Feature> {
+        while let Some(&feature) = Feature::ALL.get(self.next) {
+            self.next += 1;
+            if self.set.contains(feature) {
+                return Some(feature);
+            }
+        }
+        None
+    }
+}
+
+impl IntoIterator for FeatureSet {
+    type Item = Feature;
+    type IntoIter = Features;
+
+    fn into_iter(self) -> Features {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &FeatureSet {
+    type Item = Feature;
+    type IntoIter = Features;
+
+    fn into_iter(self) -> Features {
+        self.iter()
+    }
+}
+
+impl FromIterator<Feature> for FeatureSet {
+    fn from_iter<I>(iter: I) -> FeatureSet
+        where I: IntoIterator<Item = Feature>
+    {
+        let mut set = FeatureSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Feature> for FeatureSet {
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = Feature>
+    {
+        for feature in iter {
+            self.insert(feature);
+        }
+    }
+}
+
+impl From<Feature> for FeatureSet {
+    fn from(feature: Feature) -> FeatureSet {
+        let mut set = FeatureSet::new();
+        set.insert(feature);
+        set
+    }
+}
+
+macro_rules! set_operator {
+    ($trait_name:ident, $method:ident, $set_method:ident) => {
+        impl $trait_name for FeatureSet {
+            type Output = FeatureSet;
+
+            fn $method(self, other: FeatureSet) -> FeatureSet {
+                self.$set_method(&other)
+            }
+        }
+    }
+}
+
+set_operator!(BitOr, bitor, union);
+set_operator!(BitAnd, bitand, intersection);
+set_operator!(BitXor, bitxor, symmetric_difference);
+set_operator!(Sub, sub, difference);
+
+impl fmt::Debug for FeatureSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// The feature names separated by spaces
+impl fmt::Display for FeatureSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, feature) in self.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(feature.name())?;
+        }
+        Ok(())
+    }
+}
+
+// The features using the YMM registers.
+fn avx_features() -> FeatureSet {
+    [Feature::Avx, Feature::Avx2, Feature::Fma, Feature::F16c, Feature::Vaes,
+     Feature::Vpclmulqdq]
+        .iter().cloned().collect()
+}
+
+// The features using the ZMM and opmask registers.
+fn avx512_features() -> FeatureSet {
+    Feature::ALL.iter().cloned().filter(|f| f.name().starts_with("avx512")).collect()
+}
+
+impl Master {
+    /// Whether the processor reports the feature.
+    ///
+    /// Features of leaves that were not queried or are not supported are
+    /// reported as missing, like the named flag methods do.
+    pub fn has(&self, feature: Feature) -> bool {
+        self.leaf_registers(feature.leaf(), feature.subleaf())
+            .is_some_and(|registers| feature.is_set_in(registers))
+    }
+
+    /// Every feature the processor reports.
+    pub fn features(&self) -> FeatureSet {
+        Feature::ALL.iter().cloned().filter(|&f| self.has(f)).collect()
+    }
+
+    /// The features the processor reports and the OS allows using.
+    ///
+    /// XSAVE needs OSXSAVE, the VEX encoded features need the AVX state
+    /// and the EVEX encoded ones the AVX-512 state to be enabled; see
+    /// `os_avx_enabled` and `os_avx512_enabled`.
+    pub fn usable_features(&self) -> FeatureSet {
+        let mut features = self.features();
+        if !self.osxsave() {
+            features.remove(Feature::Xsave);
+        }
+        if !self.os_avx_enabled() {
+            features = features - avx_features();
+        }
+        if !self.os_avx512_enabled() {
+            features = features - avx512_features();
+        }
+        features
+    }
+}
+
+#[test]
+fn metadata_matches_flag_methods() {
+    for &feature in Feature::ALL {
+        // Only the bit of this feature is set, in every decoded leaf
+        let source = |leaf, subleaf| {
+            let mut registers = CpuidResult::default();
+            match leaf {
+                0x00000000 => registers.eax = 0x7,
+                0x80000000 => registers.eax = 0x80000008,
+                _ => {}
+            }
+            if (leaf, subleaf) == (feature.leaf(), feature.subleaf()) {
+                let bit = 1 << feature.bit();
+                match feature.register() {
+                    Register::Eax => registers.eax |= bit,
+                    Register::Ebx => registers.ebx |= bit,
+                    Register::Ecx => registers.ecx |= bit,
+                    Register::Edx => registers.edx |= bit,
+                }
+            }
+            registers
+        };
+        let master = Master::from_source(&source);
+
+        for &other in Feature::ALL {
+            assert_eq!(master.has(other), other == feature, "{} with {}", other, feature);
+            assert_eq!(by_method(&master, other), other == feature, "{} with {}", other, feature);
+        }
+    }
+}
+
+#[test]
+fn names_round_trip() {
+    for &feature in Feature::ALL {
+        assert_eq!(Feature::from_name(feature.name()), Some(feature));
+    }
+    assert_eq!(Feature::from_name("sse4_2"), Some(Feature::Sse4_2));
+    assert_eq!(Feature::from_name("sse4.2"), None);
+}
+
+#[test]
+fn feature_set_algebra() {
+    let baseline: FeatureSet = [Feature::Sse2, Feature::Cx8, Feature::Cmov].iter().cloned().collect();
+    let host = Master::from_source(&::haswell_source).features();
+
+    assert!(host.contains(Feature::Avx2));
+    assert!(!host.contains(Feature::Prefetchwt1));
+    assert!(baseline.is_subset(&host));
+    assert!(host.is_superset(&baseline));
+
+    let extra = host - baseline;
+    assert_eq!(extra.len(), host.len() - 3);
+    assert!(extra.is_disjoint(&baseline));
+    assert_eq!(extra | baseline, host);
+    assert_eq!(host & baseline, baseline);
+    assert_eq!(host ^ baseline, extra);
+
+    assert!(FeatureSet::all().iter().eq(Feature::ALL.iter().cloned()));
+    assert_eq!(baseline.to_string(), "cx8 cmov sse2");
+
+    let mut set = FeatureSet::new();
+    assert!(set.insert(Feature::InvariantTsc));
+    assert!(!set.insert(Feature::InvariantTsc));
+    assert!(set.remove(Feature::InvariantTsc));
+    assert!(set.is_empty());
+}
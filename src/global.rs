@@ -0,0 +1,120 @@
+//! The process-wide CPU information, detected once on first use.
+
+use core::cell::UnsafeCell;
+use core::hint;
+use core::mem::{self, MaybeUninit};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::{CpuidResult, Master};
+
+const UNINITIALIZED: u8 = 0;
+const INITIALIZING: u8 = 1;
+const INITIALIZED: u8 = 2;
+
+// A minimal spinning once-cell, usable without `std`.
+struct Global {
+    state: AtomicU8,
+    master: UnsafeCell<MaybeUninit<Master>>,
+}
+
+// The value is only written once, before `state` is released as
+// `INITIALIZED`, and is only read afterwards.
+unsafe impl Sync for Global {}
+
+impl Global {
+    const fn new() -> Global {
+        Global {
+            state: AtomicU8::new(UNINITIALIZED),
+            master: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    #[inline]
+    fn get_or_init<F>(&self, detect: F) -> &Master
+        where F: FnOnce() -> Master
+    {
+        if self.state.load(Ordering::Acquire) != INITIALIZED {
+            self.initialize(detect);
+        }
+        // Safety: `state` has been observed as `INITIALIZED`
+        unsafe { &*(*self.master.get()).as_ptr() }
+    }
+
+    #[cold]
+    fn initialize<F>(&self, detect: F)
+        where F: FnOnce() -> Master
+    {
+        let mut detect = Some(detect);
+
+        loop {
+            match self.state.compare_exchange(UNINITIALIZED, INITIALIZING,
+                                              Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => {
+                    // If `detect` panics, let another thread try again
+                    // instead of leaving it spinning forever.
+                    let reset = Reset(&self.state);
+                    let master = detect.take().expect("detection is only attempted once")();
+                    unsafe { (*self.master.get()).as_mut_ptr().write(master) };
+                    mem::forget(reset);
+                    self.state.store(INITIALIZED, Ordering::Release);
+                    return;
+                }
+                Err(INITIALIZED) => return,
+                Err(_) => hint::spin_loop(),
+            }
+        }
+    }
+}
+
+// Releases the claim on a `Global` when dropped during unwinding.
+struct Reset<'a>(&'a AtomicU8);
+
+impl<'a> Drop for Reset<'a> {
+    fn drop(&mut self) {
+        self.0.store(UNINITIALIZED, Ordering::Release);
+    }
+}
+
+static GLOBAL: Global = Global::new();
+
+/// The CPU information of the current processor, detected on the
+/// first call and shared for the rest of the process.
+///
+/// After the first call this is a single atomic load, so it can be
+/// consulted on hot paths, such as before every call of a function
+/// with several implementations. Threads that call this while another
+/// thread performs the detection spin until it is finished; should the
+/// detection panic, one of them performs it again.
+///
+/// On processors without CPUID, every flag is `false`.
+#[inline]
+pub fn get() -> &'static Master {
+    GLOBAL.get_or_init(|| {
+        ::master().unwrap_or_else(|| Master::from_source(&|_, _| CpuidResult::default()))
+    })
+}
+
+#[test]
+fn detected_once_for_all_threads() {
+    let threads: Vec<_> = (0..4)
+        .map(|_| ::std::thread::spawn(|| get() as *const Master as usize))
+        .collect();
+    let addresses: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+    assert!(addresses.iter().all(|&a| a == get() as *const Master as usize));
+    if let Some(master) = ::master() {
+        assert_eq!(format!("{:?}", get()), format!("{:?}", master));
+    }
+}
+
+#[test]
+fn panicking_detection_can_be_retried() {
+    static GLOBAL: Global = Global::new();
+
+    let panicked = ::std::thread::spawn(|| GLOBAL.get_or_init(|| panic!("detection failed")))
+        .join();
+    assert!(panicked.is_err());
+
+    let master = GLOBAL.get_or_init(|| Master::from_source(&::haswell_source));
+    assert!(master.avx2());
+}
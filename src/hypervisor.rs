@@ -0,0 +1,99 @@
+//! The hypervisor identification of leaf 0x40000000.
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::write_signature;
+
+/// The hypervisor, as identified by the signature in leaf 0x40000000.
+///
+/// The leaf is only meaningful when leaf 1 reports that a hypervisor
+/// is present; see `Master::hypervisor`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Hypervisor {
+    /// QEMU's Tiny Code Generator
+    QemuTcg,
+    Kvm,
+    VMware,
+    VirtualBox,
+    Xen,
+    HyperV,
+    Parallels,
+    Bhyve,
+    Acrn,
+    Qnx,
+    /// A signature not known to this crate
+    Unknown([u8; 12]),
+}
+
+impl Hypervisor {
+    /// Identifies the hypervisor from the 12 bytes of EBX, ECX and EDX of
+    /// leaf 0x40000000.
+    pub fn from_signature(bytes: &[u8; 12]) -> Hypervisor {
+        match bytes {
+            b"TCGTCGTCGTCG" => Hypervisor::QemuTcg,
+            b" KVMKVMKVM  " | b"KVMKVMKVM\0\0\0" => Hypervisor::Kvm,
+            b"VMwareVMware" => Hypervisor::VMware,
+            b"VBoxVBoxVBox" => Hypervisor::VirtualBox,
+            b"XenVMMXenVMM" => Hypervisor::Xen,
+            b"Microsoft Hv" => Hypervisor::HyperV,
+            b" lrpepyh  vr" | b"prl hyperv  " => Hypervisor::Parallels,
+            b"bhyve bhyve " => Hypervisor::Bhyve,
+            b"ACRNACRNACRN" => Hypervisor::Acrn,
+            b" QNXQVMBSQG " => Hypervisor::Qnx,
+            _ => Hypervisor::Unknown(*bytes),
+        }
+    }
+}
+
+impl fmt::Display for Hypervisor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Hypervisor::QemuTcg => "QEMU TCG",
+            Hypervisor::Kvm => "KVM",
+            Hypervisor::VMware => "VMware",
+            Hypervisor::VirtualBox => "VirtualBox",
+            Hypervisor::Xen => "Xen",
+            Hypervisor::HyperV => "Hyper-V",
+            Hypervisor::Parallels => "Parallels",
+            Hypervisor::Bhyve => "bhyve",
+            Hypervisor::Acrn => "ACRN",
+            Hypervisor::Qnx => "QNX Hypervisor",
+            Hypervisor::Unknown(ref bytes) => return write_signature(f, bytes),
+        };
+        f.write_str(name)
+    }
+}
+
+#[test]
+fn known_and_unknown_hypervisors() {
+    assert_eq!(Hypervisor::from_signature(b"KVMKVMKVM\0\0\0"), Hypervisor::Kvm);
+    assert_eq!(Hypervisor::from_signature(b"Microsoft Hv"), Hypervisor::HyperV);
+    assert_eq!(Hypervisor::from_signature(b"Jailhouse\0\0\0"),
+               Hypervisor::Unknown(*b"Jailhouse\0\0\0"));
+    assert_eq!(Hypervisor::HyperV.to_string(), "Hyper-V");
+}
+
+#[test]
+fn hypervisor_of_master() {
+    use super::{CpuidResult, Master};
+
+    assert_eq!(Master::from_source(&::haswell_source).hypervisor(), None);
+
+    let source = |leaf, subleaf| match leaf {
+        1 => {
+            let mut registers = ::haswell_source(leaf, subleaf);
+            registers.ecx |= 1 << 31;
+            registers
+        }
+        // "KVMKVMKVM\0\0\0"
+        0x40000000 => CpuidResult { eax: 0x40000001, ebx: 0x4B4D564B, ecx: 0x564B4D56, edx: 0x4D },
+        _ => ::haswell_source(leaf, subleaf),
+    };
+    let master = Master::from_source(&source);
+    assert_eq!(master.hypervisor(), Some(Hypervisor::Kvm));
+    assert_eq!(master.vendor(), ::Vendor::Intel);
+}
@@ -0,0 +1,135 @@
+//! The microarchitecture levels of the x86-64 psABI.
+
+use core::fmt;
+
+use super::{Feature, FeatureSet, Master};
+
+/// A microarchitecture level defined by the x86-64 psABI, such as
+/// `x86-64-v3`.
+///
+/// Levels are ordered, and each level includes the features of the
+/// levels below it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum X86_64Level {
+    V1,
+    V2,
+    V3,
+    V4,
+}
+
+impl X86_64Level {
+    pub const ALL: [X86_64Level; 4] =
+        [X86_64Level::V1, X86_64Level::V2, X86_64Level::V3, X86_64Level::V4];
+
+    /// The features added by this level.
+    fn added_features(self) -> &'static [Feature] {
+        match self {
+            X86_64Level::V1 => &[
+                Feature::Cmov, Feature::Cx8, Feature::Fpu, Feature::Fxsr, Feature::Mmx,
+                Feature::SyscallSysretIn64Bit, Feature::Sse, Feature::Sse2,
+            ],
+            X86_64Level::V2 => &[
+                Feature::Cmpxchg16b, Feature::LahfSahfIn64Bit, Feature::Popcnt, Feature::Sse3,
+                Feature::Sse4_1, Feature::Sse4_2, Feature::Ssse3,
+            ],
+            X86_64Level::V3 => &[
+                Feature::Avx, Feature::Avx2, Feature::Bmi1, Feature::Bmi2, Feature::F16c,
+                Feature::Fma, Feature::Lzcnt, Feature::Movbe, Feature::Osxsave,
+            ],
+            X86_64Level::V4 => &[
+                Feature::Avx512f, Feature::Avx512bw, Feature::Avx512cd, Feature::Avx512dq,
+                Feature::Avx512vl,
+            ],
+        }
+    }
+
+    /// Every feature required by this level, including those of the
+    /// levels below it.
+    pub fn required_features(self) -> FeatureSet {
+        X86_64Level::ALL.iter()
+            .filter(|&&level| level <= self)
+            .flat_map(|level| level.added_features().iter().cloned())
+            .collect()
+    }
+
+    /// The level above this one.
+    pub fn next(self) -> Option<X86_64Level> {
+        match self {
+            X86_64Level::V1 => Some(X86_64Level::V2),
+            X86_64Level::V2 => Some(X86_64Level::V3),
+            X86_64Level::V3 => Some(X86_64Level::V4),
+            X86_64Level::V4 => None,
+        }
+    }
+
+    /// The name used by compilers, e.g. `x86-64-v3`.
+    pub fn name(self) -> &'static str {
+        match self {
+            X86_64Level::V1 => "x86-64",
+            X86_64Level::V2 => "x86-64-v2",
+            X86_64Level::V3 => "x86-64-v3",
+            X86_64Level::V4 => "x86-64-v4",
+        }
+    }
+}
+
+impl fmt::Display for X86_64Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Master {
+    /// The features required by `level` that are missing or that the
+    /// OS does not allow using.
+    pub fn missing_for_x86_64_level(&self, level: X86_64Level) -> FeatureSet {
+        level.required_features() - self.usable_features()
+    }
+
+    /// The highest x86-64 psABI level the processor and OS satisfy, or
+    /// `None` if not even the baseline is met, as for 32-bit processors.
+    pub fn x86_64_level(&self) -> Option<X86_64Level> {
+        X86_64Level::ALL.iter()
+            .cloned()
+            .take_while(|&level| self.missing_for_x86_64_level(level).is_empty())
+            .last()
+    }
+
+    /// The level above `x86_64_level` and the features missing to reach
+    /// it, or `None` if the highest level is satisfied.
+    pub fn missing_for_next_x86_64_level(&self) -> Option<(X86_64Level, FeatureSet)> {
+        let next = match self.x86_64_level() {
+            Some(level) => level.next()?,
+            None => X86_64Level::V1,
+        };
+        Some((next, self.missing_for_x86_64_level(next)))
+    }
+}
+
+#[test]
+fn haswell_is_v3() {
+    use super::CpuidResult;
+
+    let master = Master::from_source(&::haswell_source);
+    assert_eq!(master.x86_64_level(), Some(X86_64Level::V3));
+
+    let (next, missing) = master.missing_for_next_x86_64_level().unwrap();
+    assert_eq!(next, X86_64Level::V4);
+    assert_eq!(missing.to_string(), "avx512f avx512dq avx512cd avx512bw avx512vl");
+
+    // Without POPCNT, only the baseline remains
+    let source = |leaf, subleaf| {
+        let mut registers = ::haswell_source(leaf, subleaf);
+        if leaf == 1 {
+            registers.ecx &= !(1 << 23);
+        }
+        registers
+    };
+    let master = Master::from_source(&source);
+    assert_eq!(master.x86_64_level(), Some(X86_64Level::V1));
+    assert_eq!(master.missing_for_next_x86_64_level().unwrap().1.to_string(), "popcnt");
+
+    let master = Master::from_source(&|_, _| CpuidResult::default());
+    assert_eq!(master.x86_64_level(), None);
+    assert_eq!(master.missing_for_next_x86_64_level().unwrap().0, X86_64Level::V1);
+}
@@ -1,4 +1,4 @@
-#![cfg_attr(feature = "unstable", feature(asm))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![cfg_attr(not(any(target_arch = "x86_64", target_arch = "x86")), allow(dead_code))]
 
 //! ```
@@ -14,13 +14,81 @@
 //!     }
 //! }
 //! ```
-
-
+//!
+//! # Cargo features
+//!
+//! * `std` (default) - Adds access to other logical CPUs on Linux
+//!   (`master_for_cpu`, `per_cpu`) and `std::error::Error` impls.
+//!   Implies `alloc`. Without it, the crate is `no_std`.
+//! * `alloc` - Adds the types that need a heap: `RawDump` with its
+//!   parsers, and `rustc_target_features`. Without it, only `core` is
+//!   required.
+//! * `serde` - Serialization of `Master`, the leaf structs and, with
+//!   `alloc`, `RawDump`.
+//! * `json` - Enables `--format json` in the `dump-information` binary,
+//!   e.g. `cargo install cupid --features json`. Implies `std` and
+//!   `serde`.
+
+
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "alloc")]
+extern crate alloc;
 #[macro_use]
 extern crate cfg_if;
-
-use std::{fmt, slice, str};
-use std::ops::Deref;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(all(feature = "std", target_os = "linux"))]
+extern crate libc;
+
+use core::{fmt, slice, str};
+use core::ops::Deref;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeStruct;
+
+mod cpuinfo;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod dev_cpu;
+#[cfg(feature = "alloc")]
+mod dump;
+mod error;
+mod feature;
+mod global;
+mod hypervisor;
+mod level;
+mod microarchitecture;
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+mod per_cpu;
+mod rustc;
+#[cfg(feature = "serde")]
+mod serialization;
+mod signature;
+mod target_cpu;
+mod vendor;
+
+pub use cpuinfo::CpuinfoFlags;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use dev_cpu::{master_for_cpu, DevCpuid};
+#[cfg(feature = "alloc")]
+pub use dump::{CpuidR, ParseError, RawDump, RawLeaf};
+pub use error::Error;
+pub use feature::{Feature, FeatureSet, Features, Register};
+pub use global::get;
+pub use hypervisor::Hypervisor;
+pub use level::X86_64Level;
+pub use microarchitecture::Microarchitecture;
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+pub use per_cpu::per_cpu;
+pub use rustc::RustcTargetFeatures;
+#[cfg(feature = "serde")]
+pub use serialization::Decoded;
+pub use signature::{ParseSignatureError, ProcessorSignature, ProcessorType};
+pub use vendor::Vendor;
 
 #[repr(u32)]
 enum RequestType {
@@ -28,6 +96,7 @@ enum RequestType {
     VersionInformation                = 0x00000001,
     ThermalPowerManagementInformation = 0x00000006,
     StructuredExtendedInformation     = 0x00000007,
+    HypervisorInformation             = 0x40000000,
     ExtendedFunctionInformation       = 0x80000000,
     ExtendedProcessorSignature        = 0x80000001,
     BrandString1                      = 0x80000002,
@@ -39,75 +108,188 @@ enum RequestType {
     PhysicalAddressSize               = 0x80000008,
 }
 
+/// The contents of the four registers after executing the CPUID
+/// instruction.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+/// A provider of CPUID register values.
+///
+/// `Native` executes the instruction on the current processor, but
+/// any implementation can be used to build a `Master`, such as values
+/// recorded on another machine or synthesized for a test. Closures
+/// taking the leaf and subleaf are sources as well.
+pub trait CpuidSource {
+    /// Returns the registers for the given leaf (EAX) and subleaf (ECX).
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuidResult;
+
+    /// Returns the XCR0 register, which tells which register state the
+    /// operating system saves, or `None` if it is unknown.
+    ///
+    /// Only asked for when the source reports OSXSAVE; `Native` executes
+    /// XGETBV without checking again.
+    fn xcr0(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl<F> CpuidSource for F
+    where F: Fn(u32, u32) -> CpuidResult
+{
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuidResult {
+        self(leaf, subleaf)
+    }
+}
+
+/// The CPUID instruction of the currently running processor.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Native;
+
+fn cpuid<S>(source: &S, code: RequestType) -> (u32, u32, u32, u32)
+    where S: CpuidSource + ?Sized
+{
+    let res = source.cpuid(code as u32, 0);
+    (res.eax, res.ebx, res.ecx, res.edx)
+}
+
 cfg_if! {
     if #[cfg(any(target_arch = "x86_64", target_arch = "x86"))] {
-        #[cfg(feature = "unstable")]
-        fn cpuid(code: RequestType) -> (u32, u32, u32, u32) {
-            let res1;
-            let res2;
-            let res3;
-            let res4;
-
-            unsafe {
-                asm!("cpuid"
-                     : // output operands
-                     "={eax}"(res1),
-                     "={ebx}"(res2),
-                     "={ecx}"(res3),
-                     "={edx}"(res4)
-                     : // input operands
-                     "{eax}"(code as u32),
-                     "{ecx}"(0 as u32)
-                     : // clobbers
-                     : // options
-                     );
-            }
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86 as arch;
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64 as arch;
+
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::has_cpuid;
+
+        #[cfg(target_arch = "x86_64")]
+        fn has_cpuid() -> bool {
+            // Every x86_64 processor implements CPUID
+            true
+        }
 
-            (res1, res2, res3, res4)
+        /// Executes the CPUID instruction for the given leaf (EAX) and
+        /// subleaf (ECX).
+        ///
+        /// This provides access to leaves that are not (yet) decoded by
+        /// this crate. Leaves that are not indexed ignore the subleaf;
+        /// passing 0 is conventional.
+        ///
+        /// On 32-bit processors, check `is_cpuid_available` first.
+        #[inline]
+        pub fn query(leaf: u32, subleaf: u32) -> CpuidResult {
+            // Newer compilers no longer consider the intrinsic unsafe
+            #[allow(unused_unsafe)]
+            let res = unsafe { arch::__cpuid_count(leaf, subleaf) };
+            CpuidResult { eax: res.eax, ebx: res.ebx, ecx: res.ecx, edx: res.edx }
         }
 
-        #[cfg(not(feature = "unstable"))]
-        fn cpuid(code: RequestType) -> (u32, u32, u32, u32) {
-            extern {
-                // This function name encodes an ABI compatibility
-                // version. When we release a new major version of the
-                // crate, this should be bumped to allow co-existing
-                // installations. If we need to change this interface,
-                // we should likely bump this version as well!
-                fn __cupid_cpuid_shim_0_3(code: u32, code2: u32, output: *mut u32);
-            }
+        /// Whether the processor implements the CPUID instruction.
+        ///
+        /// Every x86_64 processor does, but some 486-class and embedded
+        /// x86 processors do not. Support is detected by toggling the ID
+        /// flag of EFLAGS.
+        pub fn is_cpuid_available() -> bool {
+            has_cpuid()
+        }
 
-            let mut ret = [0; 4];
+        #[target_feature(enable = "xsave")]
+        unsafe fn xgetbv() -> u64 {
+            arch::_xgetbv(0)
+        }
 
-            unsafe {
-                __cupid_cpuid_shim_0_3(code as u32, 0, ret.as_mut_ptr());
+        impl CpuidSource for Native {
+            fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuidResult {
+                query(leaf, subleaf)
             }
 
-            (ret[0], ret[1], ret[2], ret[3])
+            fn xcr0(&self) -> Option<u64> {
+                // Only called once leaf 1 has reported OSXSAVE, so XGETBV
+                // is defined without asking CPUID again
+                Some(unsafe { xgetbv() })
+            }
         }
 
-        /// The main entrypoint to the CPU information
-        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
-        pub fn master() -> Option<Master> {
-            Some(Master::new())
+        fn check_cpuid() -> Result<(), Error> {
+            if is_cpuid_available() {
+                Ok(())
+            } else {
+                Err(Error::CpuidUnavailable)
+            }
         }
 
     } else {
 
-        fn cpuid(_code: RequestType) -> (u32, u32, u32, u32) {
-            // it's an error if anyone any gets to this point on
-            // a platform other than x86.
-            unreachable!()
+        impl CpuidSource for Native {
+            fn cpuid(&self, _leaf: u32, _subleaf: u32) -> CpuidResult {
+                // it's an error if anyone any gets to this point on
+                // a platform other than x86.
+                unreachable!()
+            }
+        }
+
+        /// Whether the processor implements the CPUID instruction.
+        pub fn is_cpuid_available() -> bool {
+            false
         }
 
-        /// The main entrypoint to the CPU information
-        pub fn master() -> Option<Master> {
-            None
+        fn check_cpuid() -> Result<(), Error> {
+            Err(Error::UnsupportedArchitecture)
         }
 
     }
 }
 
+/// The main entrypoint to the CPU information
+///
+/// Returns `None` when the processor does not implement CPUID; see
+/// `try_master` for the reason.
+pub fn master() -> Option<Master> {
+    try_master().ok()
+}
+
+/// The CPU information of the current processor, or why it is not
+/// available.
+pub fn try_master() -> Result<Master, Error> {
+    check_cpuid().map(|()| Master::new())
+}
+
+/// Records every valid leaf of the current processor
+#[cfg(feature = "alloc")]
+pub fn raw_dump() -> Option<RawDump> {
+    try_raw_dump().ok()
+}
+
+/// Records every valid leaf of the current processor, or reports why
+/// CPUID is not available.
+#[cfg(feature = "alloc")]
+pub fn try_raw_dump() -> Result<RawDump, Error> {
+    check_cpuid().map(|()| RawDump::from_source(&Native))
+}
+
+// Writes a vendor or hypervisor signature, replacing bytes that are not
+// UTF-8 like `String::from_utf8_lossy`.
+fn write_signature(f: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    let mut rest = bytes;
+    loop {
+        match str::from_utf8(rest) {
+            Ok(s) => return f.write_str(s),
+            Err(e) => {
+                let (valid, invalid) = rest.split_at(e.valid_up_to());
+                f.write_str(str::from_utf8(valid).unwrap_or(""))?;
+                f.write_str("\u{FFFD}")?;
+                rest = &invalid[e.error_len().unwrap_or(invalid.len())..];
+            }
+        }
+    }
+}
+
 // This matches the Intel Architecture guide, with bits 31 -> 0.
 // The bit positions are inclusive.
 fn bits_of(val: u32, start_bit: u8, end_bit: u8) -> u32 {
@@ -127,39 +309,79 @@ fn as_bytes(v: &u32) -> &[u8] {
     unsafe { slice::from_raw_parts(start, 4) }
 }
 
-macro_rules! bit {
-    ($reg:ident, {$($idx:expr => $name:ident),+}) => {
-        $(pub fn $name(self) -> bool {
-            ((self.$reg >> $idx) & 1) != 0
-        })+
-    }
-}
+// Implements `Debug` (and `Serialize` for `Decoded`) using the
+// decoded values and the feature flags instead of the raw registers.
+macro_rules! decoded {
+    ($kind:ident, {$($name:ident),*}) => {
+        decoded!($kind, {$($name),*}, &[] as &[Feature]);
+    };
+    ($kind:ident, {$($name:ident),*}, $features:expr) => {
+        impl fmt::Debug for $kind {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let mut s = f.debug_struct(stringify!($kind));
+                $(s.field(stringify!($name), &self.$name());)*
+                for feature in $features {
+                    s.field(feature.name(), &feature.is_set_in(self.registers()));
+                }
+                s.finish()
+            }
+        }
 
-macro_rules! dump {
-    ($me:expr, $f: expr, $sname:expr, {$($name:ident),+}) => {
-        $f.debug_struct($sname)
-            $(.field(stringify!($name), &$me.$name()))+
-            .finish()
+        #[cfg(feature = "serde")]
+        impl<'a> Serialize for Decoded<'a, $kind> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer
+            {
+                let fields: &[&str] = &[$(stringify!($name)),*];
+                let len = 4 + fields.len() + $features.len();
+                let mut state = serializer.serialize_struct(stringify!($kind), len)?;
+                let registers = self.0.registers();
+                state.serialize_field("eax", &registers.eax)?;
+                state.serialize_field("ebx", &registers.ebx)?;
+                state.serialize_field("ecx", &registers.ecx)?;
+                state.serialize_field("edx", &registers.edx)?;
+                $(state.serialize_field(stringify!($name), &self.0.$name())?;)*
+                for feature in $features {
+                    state.serialize_field(feature.name(), &feature.is_set_in(registers))?;
+                }
+                state.end()
+            }
+        }
     }
 }
 
-macro_rules! delegate_flag {
-    ($item:ident, {$($name:ident),+}) => {
-        $(pub fn $name(&self) -> bool {
-            self.$item.map(|i| i.$name()).unwrap_or(false)
-        })+
+macro_rules! leaf_registers {
+    ($name:ident, $kind:expr) => {
+        impl $name {
+            fn new<S>(source: &S) -> $name
+                where S: CpuidSource + ?Sized
+            {
+                let (a, b, c, d) = cpuid(source, $kind);
+                $name { eax: a, ebx: b, ecx: c, edx: d }
+            }
+
+            fn registers(self) -> CpuidResult {
+                CpuidResult { eax: self.eax, ebx: self.ebx, ecx: self.ecx, edx: self.edx }
+            }
+        }
     }
 }
 
 macro_rules! master_attr_reader {
-    ($name:ident, $kind:ty) => {
+    ($name:ident, $try_name:ident, $kind:ty, $group:expr) => {
         pub fn $name(&self) -> Option<&$kind> {
             self.$name.as_ref()
         }
+
+        /// Like the `Option` accessor, but says why the leaf is missing.
+        pub fn $try_name(&self) -> Result<&$kind, Error> {
+            self.$name.as_ref().ok_or_else(|| self.missing($group))
+        }
     }
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct VersionInformation {
     eax: u32,
     ebx: u32,
@@ -167,12 +389,9 @@ pub struct VersionInformation {
     edx: u32,
 }
 
-impl VersionInformation {
-    fn new() -> VersionInformation {
-        let (a, b, c, d) = cpuid(RequestType::VersionInformation);
-        VersionInformation { eax: a, ebx: b, ecx: c, edx: d }
-    }
+leaf_registers!(VersionInformation, RequestType::VersionInformation);
 
+impl VersionInformation {
     pub fn family_id(self) -> u32 {
         let family_id = bits_of(self.eax, 8, 11);
         let extended_family_id = bits_of(self.eax, 20, 27);
@@ -184,8 +403,11 @@ impl VersionInformation {
         }
     }
 
+    /// The model, using Intel's rule for the extended model, which also
+    /// gives the right model for AMD's families 0xF and above. See
+    /// `Master::processor_signature` for the rule of other vendors.
     pub fn model_id(self) -> u32 {
-        let family_id = self.family_id();
+        let family_id = bits_of(self.eax, 8, 11);
         let model_id = bits_of(self.eax, 4, 7);
         let extended_model_id = bits_of(self.eax, 16, 19);
 
@@ -251,197 +473,27 @@ impl VersionInformation {
             _ => None,
         }
     }
-
-    bit!(ecx, {
-         0 => sse3,
-         1 => pclmulqdq,
-         2 => dtes64,
-         3 => monitor,
-         4 => ds_cpl,
-         5 => vmx,
-         6 => smx,
-         7 => eist,
-         8 => tm2,
-         9 => ssse3,
-        10 => cnxt_id,
-        11 => sdbg,
-        12 => fma,
-        13 => cmpxchg16b,
-        14 => xtpr_update_control,
-        15 => pdcm,
-        // 16 - reserved
-        17 => pcid,
-        18 => dca,
-        19 => sse4_1,
-        20 => sse4_2,
-        21 => x2apic,
-        22 => movbe,
-        23 => popcnt,
-        24 => tsc_deadline,
-        25 => aesni,
-        26 => xsave,
-        27 => osxsave,
-        28 => avx,
-        29 => f16c,
-        30 => rdrand
-        // 31 - unused
-    });
-
-    bit!(edx, {
-        0 => fpu,
-        1 => vme,
-        2 => de,
-        3 => pse,
-        4 => tsc,
-        5 => msr,
-        6 => pae,
-        7 => mce,
-        8 => cx8,
-        9 => apic,
-        // 10 - reserved
-        11 => sep,
-        12 => mtrr,
-        13 => pge,
-        14 => mca,
-        15 => cmov,
-        16 => pat,
-        17 => pse_36,
-        18 => psn,
-        19 => clfsh,
-        // 20 - reserved
-        21 => ds,
-        22 => acpi,
-        23 => mmx,
-        24 => fxsr,
-        25 => sse,
-        26 => sse2,
-        27 => ss,
-        28 => htt,
-        29 => tm,
-        // 30 -reserved
-        31 => pbe
-    });
-}
-
-impl fmt::Debug for VersionInformation {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "VersionInformation", {
-            family_id,
-            model_id,
-            stepping,
-            brand_string,
-            sse3,
-            pclmulqdq,
-            dtes64,
-            monitor,
-            ds_cpl,
-            vmx,
-            smx,
-            eist,
-            tm2,
-            ssse3,
-            cnxt_id,
-            sdbg,
-            fma,
-            cmpxchg16b,
-            xtpr_update_control,
-            pdcm,
-            pcid,
-            dca,
-            sse4_1,
-            sse4_2,
-            x2apic,
-            movbe,
-            popcnt,
-            tsc_deadline,
-            aesni,
-            xsave,
-            osxsave,
-            avx,
-            f16c,
-            rdrand,
-            fpu,
-            vme,
-            de,
-            pse,
-            tsc,
-            msr,
-            pae,
-            mce,
-            cx8,
-            apic,
-            sep,
-            mtrr,
-            pge,
-            mca,
-            cmov,
-            pat,
-            pse_36,
-            psn,
-            clfsh,
-            ds,
-            acpi,
-            mmx,
-            fxsr,
-            sse,
-            sse2,
-            ss,
-            htt,
-            tm,
-            pbe
-        })
-    }
 }
 
+decoded!(VersionInformation, {
+    family_id,
+    model_id,
+    stepping,
+    brand_string
+}, VersionInformation::FEATURES);
+
 #[derive(Copy,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ExtendedProcessorSignature {
+    eax: u32,
+    ebx: u32,
     ecx: u32,
     edx: u32,
 }
 
-impl ExtendedProcessorSignature {
-    fn new() -> ExtendedProcessorSignature {
-        let (_, _, c, d) = cpuid(RequestType::ExtendedProcessorSignature);
-        ExtendedProcessorSignature { ecx: c, edx: d }
-    }
-
-    bit!(ecx, {
-        0 => lahf_sahf_in_64_bit,
-        // 1-4 reserved
-        5 => lzcnt,
-        // 6-7 reserved
-        8 => prefetchw
-        // 9-31 reserved
-    });
-
-    bit!(edx, {
-        // 0-10 reserved
-        11 => syscall_sysret_in_64_bit,
-        // 12-19 reserved
-        20 => execute_disable,
-        // 21-25 reserved
-        26 => gigabyte_pages,
-        27 => rdtscp_and_ia32_tsc_aux,
-        // 28 reserved
-        29 => intel_64_bit_architecture
-        // 30-31 reserved
-    });
-}
-
-impl fmt::Debug for ExtendedProcessorSignature {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "ThermalPowerManagementInformation", {
-            lahf_sahf_in_64_bit,
-            lzcnt,
-            prefetchw,
-            syscall_sysret_in_64_bit,
-            execute_disable,
-            gigabyte_pages,
-            rdtscp_and_ia32_tsc_aux,
-            intel_64_bit_architecture
-        })
-    }
-}
+leaf_registers!(ExtendedProcessorSignature, RequestType::ExtendedProcessorSignature);
+
+decoded!(ExtendedProcessorSignature, {}, ExtendedProcessorSignature::FEATURES);
 
 // 3 calls of 4 registers of 4 bytes
 const BRAND_STRING_LENGTH: usize = 3 * 4 * 4;
@@ -451,9 +503,13 @@ pub struct BrandString {
 }
 
 impl BrandString {
-    fn new() -> BrandString {
-        fn append_bytes(a: RequestType, bytes: &mut [u8]) {
-            let (a, b, c, d) = cpuid(a);
+    fn new<S>(source: &S) -> BrandString
+        where S: CpuidSource + ?Sized
+    {
+        fn append_bytes<S>(source: &S, a: RequestType, bytes: &mut [u8])
+            where S: CpuidSource + ?Sized
+        {
+            let (a, b, c, d) = cpuid(source, a);
 
             let result_bytes =
                 as_bytes(&a).iter()
@@ -467,11 +523,20 @@ impl BrandString {
         }
 
         let mut brand_string = BrandString { bytes: [0; BRAND_STRING_LENGTH] };
-        append_bytes(RequestType::BrandString1, &mut brand_string.bytes[0..]);
-        append_bytes(RequestType::BrandString2, &mut brand_string.bytes[16..]);
-        append_bytes(RequestType::BrandString3, &mut brand_string.bytes[32..]);
+        append_bytes(source, RequestType::BrandString1, &mut brand_string.bytes[0..]);
+        append_bytes(source, RequestType::BrandString2, &mut brand_string.bytes[16..]);
+        append_bytes(source, RequestType::BrandString3, &mut brand_string.bytes[32..]);
         brand_string
     }
+
+    // The registers of the nth brand string leaf
+    fn registers(&self, part: usize) -> CpuidResult {
+        let bytes = &self.bytes[part * 16..];
+        let register = |i: usize| {
+            u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+        };
+        CpuidResult { eax: register(0), ebx: register(4), ecx: register(8), edx: register(12) }
+    }
 }
 
 impl Clone for BrandString {
@@ -480,7 +545,7 @@ impl Clone for BrandString {
         for (d, s) in bytes.iter_mut().zip(self.bytes.iter()) {
             *d = *s;
         }
-        BrandString { bytes: bytes }
+        BrandString { bytes }
     }
 }
 
@@ -490,7 +555,13 @@ impl Deref for BrandString {
     fn deref(&self) -> &str {
         let nul_terminator = self.bytes.iter().position(|&b| b == 0).unwrap_or(0);
         let usable_bytes = &self.bytes[..nul_terminator];
-        unsafe { str::from_utf8_unchecked(usable_bytes) }.trim()
+        // The registers may come from any `CpuidSource`, so stop at the
+        // first byte that is not UTF-8.
+        let valid = match str::from_utf8(usable_bytes) {
+            Ok(s) => s,
+            Err(e) => str::from_utf8(&usable_bytes[..e.valid_up_to()]).unwrap_or(""),
+        };
+        valid.trim()
     }
 }
 
@@ -507,137 +578,41 @@ impl fmt::Debug for BrandString {
 }
 
 #[derive(Copy,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ThermalPowerManagementInformation {
     eax: u32,
     ebx: u32,
     ecx: u32,
+    edx: u32,
 }
 
-impl ThermalPowerManagementInformation {
-    fn new() -> ThermalPowerManagementInformation {
-        let (a, b, c, _) = cpuid(RequestType::ThermalPowerManagementInformation);
-        ThermalPowerManagementInformation { eax: a, ebx: b, ecx: c }
-    }
-
-    bit!(eax, {
-        0 => digital_temperature_sensor,
-        1 => intel_turbo_boost,
-        2 => arat,
-        // 3 - reserved
-        4 => pln,
-        5 => ecmd,
-        6 => ptm,
-        7 => hwp,
-        8 => hwp_notification,
-        9 => hwp_activity_window,
-        10 => hwp_energy_performance_preference,
-        // 12 - reserved
-        13 => hdc
-    });
+leaf_registers!(ThermalPowerManagementInformation, RequestType::ThermalPowerManagementInformation);
 
+impl ThermalPowerManagementInformation {
     pub fn number_of_interrupt_thresholds(self) -> u32 {
         bits_of(self.ebx, 0, 3)
     }
-
-    bit!(ecx, {
-        0 => hardware_coordination_feedback,
-        // 1-2 - reserved
-        3 => performance_energy_bias
-    });
 }
 
-impl fmt::Debug for ThermalPowerManagementInformation {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "ThermalPowerManagementInformation", {
-            digital_temperature_sensor,
-            intel_turbo_boost,
-            arat,
-            pln,
-            ecmd,
-            ptm,
-            hwp,
-            hwp_notification,
-            hwp_activity_window,
-            hwp_energy_performance_preference,
-            hdc,
-
-            number_of_interrupt_thresholds,
-
-            hardware_coordination_feedback,
-            performance_energy_bias
-        })
-    }
-}
+decoded!(ThermalPowerManagementInformation, {
+    number_of_interrupt_thresholds
+}, ThermalPowerManagementInformation::FEATURES);
 
 #[derive(Copy,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StructuredExtendedInformation {
+    eax: u32,
     ebx: u32,
     ecx: u32,
+    edx: u32,
 }
 
-impl StructuredExtendedInformation {
-    fn new() -> StructuredExtendedInformation {
-        let (_, b, c, _) = cpuid(RequestType::StructuredExtendedInformation);
-        StructuredExtendedInformation { ebx: b, ecx: c }
-    }
-
-    bit!(ebx, {
-        0 => fsgsbase,
-        1 => ia32_tsc_adjust_msr,
-        // 2 - reserved
-        3 => bmi1,
-        4 => hle,
-        5 => avx2,
-        // 6 - reserved
-        7 => smep,
-        8 => bmi2,
-        9 => enhanced_rep_movsb_stosb,
-        10 => invpcid,
-        11 => rtm,
-        12 => pqm,
-        13 => deprecates_fpu_cs_ds,
-        // 14 - reserved
-        15 => pqe,
-        // 16-17 - reserved
-        18 => rdseed,
-        19 => adx,
-        20 => smap,
-        // 21-24 - reserved
-        25 => intel_processor_trace
-        // 26-31 - reserved
-    });
-
-    bit!(ecx, {
-        0 => prefetchwt1
-    });
-}
-
-impl fmt::Debug for StructuredExtendedInformation {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "StructuredExtendedInformation", {
-            fsgsbase,
-            ia32_tsc_adjust_msr,
-            bmi1,
-            hle,
-            avx2,
-            smep,
-            bmi2,
-            enhanced_rep_movsb_stosb,
-            invpcid,
-            rtm,
-            pqm,
-            deprecates_fpu_cs_ds,
-            pqe,
-            rdseed,
-            adx,
-            smap,
-            intel_processor_trace,
-            prefetchwt1
-        })
-    }
-}
+leaf_registers!(StructuredExtendedInformation, RequestType::StructuredExtendedInformation);
+
+decoded!(StructuredExtendedInformation, {}, StructuredExtendedInformation::FEATURES);
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CacheLineAssociativity {
     Disabled,
     DirectMapped,
@@ -649,20 +624,23 @@ pub enum CacheLineAssociativity {
 }
 
 #[derive(Copy, Clone)]
-pub struct CacheLine(u32);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CacheLine {
+    eax: u32,
+    ebx: u32,
+    ecx: u32,
+    edx: u32,
+}
 
-impl CacheLine {
-    fn new() -> CacheLine {
-        let (_, _, c, _) = cpuid(RequestType::CacheLine);
-        CacheLine(c)
-    }
+leaf_registers!(CacheLine, RequestType::CacheLine);
 
+impl CacheLine {
     pub fn cache_line_size(self) -> u32 {
-        bits_of(self.0, 0, 7)
+        bits_of(self.ecx, 0, 7)
     }
 
     pub fn l2_associativity(self) -> Option<CacheLineAssociativity> {
-        match bits_of(self.0, 12, 15) {
+        match bits_of(self.ecx, 12, 15) {
             0x00 => Some(CacheLineAssociativity::Disabled),
             0x01 => Some(CacheLineAssociativity::DirectMapped),
             0x02 => Some(CacheLineAssociativity::TwoWay),
@@ -675,70 +653,182 @@ impl CacheLine {
     }
 
     pub fn cache_size(self) -> u32 {
-        bits_of(self.0, 16, 31)
+        bits_of(self.ecx, 16, 31)
     }
 }
 
-impl fmt::Debug for CacheLine {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "CacheLine", {
-            cache_line_size,
-            l2_associativity,
-            cache_size
-        })
-    }
-}
+decoded!(CacheLine, {
+    cache_line_size,
+    l2_associativity,
+    cache_size
+});
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TimeStampCounter {
+    eax: u32,
+    ebx: u32,
+    ecx: u32,
     edx: u32,
 }
 
-impl TimeStampCounter {
-    fn new() -> TimeStampCounter {
-        let (_, _, _, d) = cpuid(RequestType::TimeStampCounter);
-        TimeStampCounter { edx: d }
-    }
+leaf_registers!(TimeStampCounter, RequestType::TimeStampCounter);
 
-    bit!(edx, {
-        // 0-7 - reserved
-        8 => invariant_tsc
-        // 9-31 - reserved
-    });
+decoded!(TimeStampCounter, {}, TimeStampCounter::FEATURES);
+
+#[derive(Copy,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PhysicalAddressSize {
+    eax: u32,
+    ebx: u32,
+    ecx: u32,
+    edx: u32,
 }
 
-impl fmt::Debug for TimeStampCounter {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "TimeStampCounter", {
-            invariant_tsc
-        })
+leaf_registers!(PhysicalAddressSize, RequestType::PhysicalAddressSize);
+
+impl PhysicalAddressSize {
+    pub fn physical_address_bits(self) -> u32 {
+        bits_of(self.eax, 0, 7)
+    }
+
+    pub fn linear_address_bits(self) -> u32 {
+        bits_of(self.eax, 8, 15)
     }
 }
 
-#[derive(Copy,Clone)]
-pub struct PhysicalAddressSize(u32);
+decoded!(PhysicalAddressSize, {
+    physical_address_bits,
+    linear_address_bits
+});
+
+/// A group of leaves that `Master` queries and decodes together.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LeafGroup {
+    /// Leaf 0, the vendor and the maximum standard leaf
+    BasicInformation,
+    VersionInformation,
+    ThermalPowerManagementInformation,
+    StructuredExtendedInformation,
+    ExtendedProcessorSignature,
+    /// Leaves 0x80000002 through 0x80000004
+    BrandString,
+    CacheLine,
+    TimeStampCounter,
+    PhysicalAddressSize,
+}
 
-impl PhysicalAddressSize {
-    fn new() -> PhysicalAddressSize {
-        let (a, _, _, _) = cpuid(RequestType::PhysicalAddressSize);
-        PhysicalAddressSize(a)
+impl LeafGroup {
+    pub const ALL: [LeafGroup; 9] = [
+        LeafGroup::BasicInformation,
+        LeafGroup::VersionInformation,
+        LeafGroup::ThermalPowerManagementInformation,
+        LeafGroup::StructuredExtendedInformation,
+        LeafGroup::ExtendedProcessorSignature,
+        LeafGroup::BrandString,
+        LeafGroup::CacheLine,
+        LeafGroup::TimeStampCounter,
+        LeafGroup::PhysicalAddressSize,
+    ];
+
+    /// The highest leaf the group needs.
+    pub fn leaf(self) -> u32 {
+        let kind = match self {
+            LeafGroup::BasicInformation => RequestType::BasicInformation,
+            LeafGroup::VersionInformation => RequestType::VersionInformation,
+            LeafGroup::ThermalPowerManagementInformation => {
+                RequestType::ThermalPowerManagementInformation
+            }
+            LeafGroup::StructuredExtendedInformation => RequestType::StructuredExtendedInformation,
+            LeafGroup::ExtendedProcessorSignature => RequestType::ExtendedProcessorSignature,
+            LeafGroup::BrandString => RequestType::BrandString3,
+            LeafGroup::CacheLine => RequestType::CacheLine,
+            LeafGroup::TimeStampCounter => RequestType::TimeStampCounter,
+            LeafGroup::PhysicalAddressSize => RequestType::PhysicalAddressSize,
+        };
+        kind as u32
     }
 
-    pub fn physical_address_bits(self) -> u32 {
-        bits_of(self.0, 0, 7)
+    fn is_extended(self) -> bool {
+        !matches!(self,
+                  LeafGroup::BasicInformation |
+                  LeafGroup::VersionInformation |
+                  LeafGroup::ThermalPowerManagementInformation |
+                  LeafGroup::StructuredExtendedInformation)
     }
 
-    pub fn linear_address_bits(self) -> u32 {
-        bits_of(self.0, 8, 15)
+    fn mask(self) -> u16 {
+        1 << self as u16
     }
 }
 
-impl fmt::Debug for PhysicalAddressSize {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        dump!(self, f, "PhysicalAddressSize", {
-            physical_address_bits,
-            linear_address_bits
-        })
+/// Builds a `Master` from a selection of leaf groups.
+///
+/// Only the selected groups, and the leaf reporting the maximum leaf
+/// of their range, are queried. Other groups can be added to the
+/// resulting `Master` later using `Master::fetch`.
+///
+/// ```
+/// use cupid::{LeafGroup, MasterBuilder};
+///
+/// // Executes only leaves 0x80000000 through 0x80000004
+/// let information = MasterBuilder::new().with(LeafGroup::BrandString).build();
+/// if let Some(information) = information {
+///     println!("{:?}", information.brand_string());
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MasterBuilder {
+    groups: u16,
+}
+
+impl MasterBuilder {
+    /// A builder without any groups selected
+    pub fn new() -> MasterBuilder {
+        MasterBuilder::default()
+    }
+
+    /// A builder with every group selected
+    pub fn all() -> MasterBuilder {
+        LeafGroup::ALL.iter().fold(MasterBuilder::new(), |b, &g| b.with(g))
+    }
+
+    pub fn with(mut self, group: LeafGroup) -> MasterBuilder {
+        self.groups |= group.mask();
+        self
+    }
+
+    pub fn without(mut self, group: LeafGroup) -> MasterBuilder {
+        self.groups &= !group.mask();
+        self
+    }
+
+    pub fn contains(&self, group: LeafGroup) -> bool {
+        self.groups & group.mask() != 0
+    }
+
+    /// Queries the selected groups of the current processor.
+    ///
+    /// Returns `None` when the processor does not implement CPUID.
+    pub fn build(&self) -> Option<Master> {
+        self.try_build().ok()
+    }
+
+    /// Queries the selected groups of the current processor, or reports
+    /// why CPUID is not available.
+    pub fn try_build(&self) -> Result<Master, Error> {
+        check_cpuid().map(|()| self.build_from_source(&Native))
+    }
+
+    /// Queries the selected groups of the given source.
+    pub fn build_from_source<S>(&self, source: &S) -> Master
+        where S: CpuidSource + ?Sized
+    {
+        let mut master = Master::empty();
+        for &group in LeafGroup::ALL.iter().filter(|&&g| self.contains(g)) {
+            master.fetch(source, group);
+        }
+        master
     }
 }
 
@@ -752,8 +842,11 @@ impl fmt::Debug for PhysicalAddressSize {
 /// For data beyond simple feature flags, you will need to retrieve
 /// the nested struct and call the appropriate methods on it.
 #[derive(Debug,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Master {
     // TODO: Rename struct
+    basic_information: Option<CpuidResult>,
+    extended_function_information: Option<CpuidResult>,
     version_information: Option<VersionInformation>,
     thermal_power_management_information: Option<ThermalPowerManagementInformation>,
     structured_extended_information: Option<StructuredExtendedInformation>,
@@ -762,209 +855,508 @@ pub struct Master {
     cache_line: Option<CacheLine>,
     time_stamp_counter: Option<TimeStampCounter>,
     physical_address_size: Option<PhysicalAddressSize>,
+    hypervisor_information: Option<CpuidResult>,
+    xcr0: Option<u64>,
 }
 
 impl Master {
     fn new() -> Master {
-        fn when_supported<F, T>(max: u32, kind: RequestType, then: F) -> Option<T>
+        Master::from_source(&Native)
+    }
+
+    /// Decodes the CPU information provided by the given source
+    pub fn from_source<S>(source: &S) -> Master
+        where S: CpuidSource + ?Sized
+    {
+        MasterBuilder::all().build_from_source(source)
+    }
+
+    fn empty() -> Master {
+        Master {
+            basic_information: None,
+            extended_function_information: None,
+            version_information: None,
+            thermal_power_management_information: None,
+            structured_extended_information: None,
+            extended_processor_signature: None,
+            brand_string: None,
+            cache_line: None,
+            time_stamp_counter: None,
+            physical_address_size: None,
+            hypervisor_information: None,
+            xcr0: None,
+        }
+    }
+
+    /// Queries a group of leaves that was not selected when building.
+    ///
+    /// Groups that have already been queried or that are not supported
+    /// by the processor are left as they are.
+    pub fn fetch<S>(&mut self, source: &S, group: LeafGroup)
+        where S: CpuidSource + ?Sized
+    {
+        fn when_missing<F, T>(slot: &mut Option<T>, max: u32, kind: RequestType, then: F)
             where F: FnOnce() -> T
         {
-            if max >= kind as u32 {
-                Some(then())
-            } else {
-                None
+            if slot.is_none() && max >= kind as u32 {
+                *slot = Some(then())
             }
         }
 
-        let (max_value, _, _, _) = cpuid(RequestType::BasicInformation);
-
-        let vi = when_supported(max_value, RequestType::VersionInformation, || {
-            VersionInformation::new()
-        });
-        let tpm = when_supported(max_value, RequestType::ThermalPowerManagementInformation, || {
-            ThermalPowerManagementInformation::new()
-        });
-        let sei = when_supported(max_value, RequestType::StructuredExtendedInformation, || {
-            StructuredExtendedInformation::new()
-        });
-
-        // Extended information
-
-        let (max_value, _, _, _) = cpuid(RequestType::ExtendedFunctionInformation);
-
-        let eps = when_supported(max_value, RequestType::ExtendedProcessorSignature, || {
-            ExtendedProcessorSignature::new()
-        });
-        let brand_string = when_supported(max_value, RequestType::BrandString3, || {
-            BrandString::new()
-        });
-        let cache_line = when_supported(max_value, RequestType::CacheLine, || {
-            CacheLine::new()
-        });
-        let tsc = when_supported(max_value, RequestType::TimeStampCounter, || {
-            TimeStampCounter::new()
-        });
-        let pas = when_supported(max_value, RequestType::PhysicalAddressSize, || {
-            PhysicalAddressSize::new()
-        });
+        if !group.is_extended() {
+            let max_value = self.basic_information.get_or_insert_with(|| {
+                source.cpuid(RequestType::BasicInformation as u32, 0)
+            }).eax;
+
+            match group {
+                LeafGroup::VersionInformation => {
+                    when_missing(&mut self.version_information, max_value,
+                                 RequestType::VersionInformation,
+                                 || VersionInformation::new(source));
+                    if self.xcr0.is_none() && self.osxsave() {
+                        self.xcr0 = source.xcr0();
+                    }
+                    if self.hypervisor_information.is_none() && self.hypervisor_present() {
+                        self.hypervisor_information =
+                            Some(source.cpuid(RequestType::HypervisorInformation as u32, 0));
+                    }
+                }
+                LeafGroup::ThermalPowerManagementInformation => {
+                    when_missing(&mut self.thermal_power_management_information, max_value,
+                                 RequestType::ThermalPowerManagementInformation,
+                                 || ThermalPowerManagementInformation::new(source))
+                }
+                LeafGroup::StructuredExtendedInformation => {
+                    when_missing(&mut self.structured_extended_information, max_value,
+                                 RequestType::StructuredExtendedInformation,
+                                 || StructuredExtendedInformation::new(source))
+                }
+                _ => {}
+            }
+        } else {
+            let max_value = self.extended_function_information.get_or_insert_with(|| {
+                source.cpuid(RequestType::ExtendedFunctionInformation as u32, 0)
+            }).eax;
+
+            match group {
+                LeafGroup::ExtendedProcessorSignature => {
+                    when_missing(&mut self.extended_processor_signature, max_value,
+                                 RequestType::ExtendedProcessorSignature,
+                                 || ExtendedProcessorSignature::new(source))
+                }
+                LeafGroup::BrandString => {
+                    when_missing(&mut self.brand_string, max_value,
+                                 RequestType::BrandString3,
+                                 || BrandString::new(source))
+                }
+                LeafGroup::CacheLine => {
+                    when_missing(&mut self.cache_line, max_value,
+                                 RequestType::CacheLine,
+                                 || CacheLine::new(source))
+                }
+                LeafGroup::TimeStampCounter => {
+                    when_missing(&mut self.time_stamp_counter, max_value,
+                                 RequestType::TimeStampCounter,
+                                 || TimeStampCounter::new(source))
+                }
+                LeafGroup::PhysicalAddressSize => {
+                    when_missing(&mut self.physical_address_size, max_value,
+                                 RequestType::PhysicalAddressSize,
+                                 || PhysicalAddressSize::new(source))
+                }
+                _ => {}
+            }
+        }
+    }
 
-        Master {
-            version_information: vi,
-            thermal_power_management_information: tpm,
-            structured_extended_information: sei,
-            extended_processor_signature: eps,
-            brand_string: brand_string,
-            cache_line: cache_line,
-            time_stamp_counter: tsc,
-            physical_address_size: pas,
+    /// The registers of every leaf that has been decoded.
+    ///
+    /// Only the leaves understood by `Master` are included; use
+    /// `RawDump::from_source` to record every leaf of a processor.
+    #[cfg(feature = "alloc")]
+    pub fn raw_dump(&self) -> RawDump {
+        let mut dump = RawDump::empty();
+        {
+            let mut record = |kind: RequestType, registers: Option<CpuidResult>| {
+                if let Some(registers) = registers {
+                    dump.insert(kind as u32, 0, registers);
+                }
+            };
+
+            let bs = self.brand_string.as_ref();
+
+            record(RequestType::BasicInformation, self.basic_information);
+            record(RequestType::VersionInformation,
+                   self.version_information.map(VersionInformation::registers));
+            record(RequestType::ThermalPowerManagementInformation,
+                   self.thermal_power_management_information.map(ThermalPowerManagementInformation::registers));
+            record(RequestType::StructuredExtendedInformation,
+                   self.structured_extended_information.map(StructuredExtendedInformation::registers));
+            record(RequestType::HypervisorInformation, self.hypervisor_information);
+            record(RequestType::ExtendedFunctionInformation, self.extended_function_information);
+            record(RequestType::ExtendedProcessorSignature,
+                   self.extended_processor_signature.map(ExtendedProcessorSignature::registers));
+            record(RequestType::BrandString1, bs.map(|bs| bs.registers(0)));
+            record(RequestType::BrandString2, bs.map(|bs| bs.registers(1)));
+            record(RequestType::BrandString3, bs.map(|bs| bs.registers(2)));
+            record(RequestType::CacheLine, self.cache_line.map(CacheLine::registers));
+            record(RequestType::TimeStampCounter, self.time_stamp_counter.map(TimeStampCounter::registers));
+            record(RequestType::PhysicalAddressSize,
+                   self.physical_address_size.map(PhysicalAddressSize::registers));
+        }
+        dump
+    }
+
+    // The registers of a decoded leaf, if it has been queried.
+    fn leaf_registers(&self, leaf: u32, subleaf: u32) -> Option<CpuidResult> {
+        if subleaf != 0 {
+            return None;
+        }
+
+        let bs = self.brand_string.as_ref();
+
+        match leaf {
+            0x00000000 => self.basic_information,
+            0x00000001 => self.version_information.map(VersionInformation::registers),
+            0x00000006 => {
+                self.thermal_power_management_information
+                    .map(ThermalPowerManagementInformation::registers)
+            }
+            0x00000007 => {
+                self.structured_extended_information.map(StructuredExtendedInformation::registers)
+            }
+            0x40000000 => self.hypervisor_information,
+            0x80000000 => self.extended_function_information,
+            0x80000001 => self.extended_processor_signature.map(ExtendedProcessorSignature::registers),
+            0x80000002 => bs.map(|bs| bs.registers(0)),
+            0x80000003 => bs.map(|bs| bs.registers(1)),
+            0x80000004 => bs.map(|bs| bs.registers(2)),
+            0x80000006 => self.cache_line.map(CacheLine::registers),
+            0x80000007 => self.time_stamp_counter.map(TimeStampCounter::registers),
+            0x80000008 => self.physical_address_size.map(PhysicalAddressSize::registers),
+            _ => None,
+        }
+    }
+
+    master_attr_reader!(version_information, try_version_information, VersionInformation,
+                        LeafGroup::VersionInformation);
+    master_attr_reader!(thermal_power_management_information, try_thermal_power_management_information, ThermalPowerManagementInformation,
+                        LeafGroup::ThermalPowerManagementInformation);
+    master_attr_reader!(structured_extended_information, try_structured_extended_information, StructuredExtendedInformation,
+                        LeafGroup::StructuredExtendedInformation);
+    master_attr_reader!(extended_processor_signature, try_extended_processor_signature, ExtendedProcessorSignature,
+                        LeafGroup::ExtendedProcessorSignature);
+    master_attr_reader!(cache_line, try_cache_line, CacheLine,
+                        LeafGroup::CacheLine);
+    master_attr_reader!(time_stamp_counter, try_time_stamp_counter, TimeStampCounter,
+                        LeafGroup::TimeStampCounter);
+    master_attr_reader!(physical_address_size, try_physical_address_size, PhysicalAddressSize,
+                        LeafGroup::PhysicalAddressSize);
+
+    /// The 12 byte vendor identification string, such as `GenuineIntel`
+    ///
+    /// All bytes are zero if leaf 0 has not been queried.
+    pub fn vendor_string(&self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        let basic_information = self.basic_information.unwrap_or_default();
+        let registers = [basic_information.ebx,
+                         basic_information.edx,
+                         basic_information.ecx];
+        for (chunk, register) in bytes.chunks_mut(4).zip(registers.iter()) {
+            chunk.copy_from_slice(&register.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// The vendor identified by leaf 0, or a zeroed `Vendor::Unknown`
+    /// if leaf 0 has not been queried.
+    pub fn vendor(&self) -> Vendor {
+        match self.basic_information {
+            Some(_) => Vendor::from_vendor_string(&self.vendor_string()),
+            None => Vendor::Unknown([0; 12]),
+        }
+    }
+
+    // Whether leaf 1 reports that the processor runs under a hypervisor.
+    fn hypervisor_present(&self) -> bool {
+        self.version_information.is_some_and(|vi| bits_of(vi.ecx, 31, 31) == 1)
+    }
+
+    /// The hypervisor identified by leaf 0x40000000, or `None` if leaf 1
+    /// was not queried or does not report a hypervisor.
+    pub fn hypervisor(&self) -> Option<Hypervisor> {
+        let registers = self.hypervisor_information?;
+        let mut bytes = [0; 12];
+        for (chunk, register) in bytes.chunks_mut(4)
+            .zip([registers.ebx, registers.ecx, registers.edx].iter())
+        {
+            chunk.copy_from_slice(&register.to_le_bytes());
         }
+        Some(Hypervisor::from_signature(&bytes))
     }
 
-    master_attr_reader!(version_information, VersionInformation);
-    master_attr_reader!(thermal_power_management_information, ThermalPowerManagementInformation);
-    master_attr_reader!(structured_extended_information, StructuredExtendedInformation);
-    master_attr_reader!(extended_processor_signature, ExtendedProcessorSignature);
-    master_attr_reader!(cache_line, CacheLine);
-    master_attr_reader!(time_stamp_counter, TimeStampCounter);
-    master_attr_reader!(physical_address_size, PhysicalAddressSize);
+    // Why the leaves of a group are not available.
+    fn missing(&self, group: LeafGroup) -> Error {
+        let max_leaf = if group.is_extended() {
+            self.extended_function_information
+        } else {
+            self.basic_information
+        };
+
+        match max_leaf {
+            Some(max_leaf) if max_leaf.eax < group.leaf() => {
+                Error::LeafNotSupported { leaf: group.leaf(), max: max_leaf.eax }
+            }
+            _ => Error::NotQueried(group),
+        }
+    }
+
+    /// The XCR0 register, if the source provides it.
+    pub fn xcr0(&self) -> Option<u64> {
+        self.xcr0
+    }
+
+    // Whether the OS saves all of the given XCR0 state components. When
+    // XCR0 is unknown, as for recorded sources, OSXSAVE is trusted.
+    fn os_saves(&self, components: u64) -> bool {
+        let saved = match self.xcr0 {
+            Some(xcr0) => xcr0 & components == components,
+            None => true,
+        };
+        self.osxsave() && saved
+    }
+
+    /// Whether the OS saves the SSE and AVX register state, which is
+    /// required to use AVX, FMA and the other VEX encoded instructions.
+    pub fn os_avx_enabled(&self) -> bool {
+        self.os_saves(0b0000_0110)
+    }
+
+    /// Whether the OS saves the AVX-512 opmask and ZMM register state in
+    /// addition to the AVX state.
+    pub fn os_avx512_enabled(&self) -> bool {
+        self.os_saves(0b1110_0110)
+    }
+
+    /// Like `vendor`, but fails if leaf 0 has not been queried.
+    pub fn try_vendor(&self) -> Result<Vendor, Error> {
+        match self.basic_information {
+            Some(_) => Ok(self.vendor()),
+            None => Err(Error::NotQueried(LeafGroup::BasicInformation)),
+        }
+    }
+
+    /// Like `brand_string`, but says why the extended brand string
+    /// leaves are missing when there is no brand index to fall back on.
+    pub fn try_brand_string(&self) -> Result<&str, Error> {
+        self.brand_string().ok_or_else(|| self.missing(LeafGroup::BrandString))
+    }
 
     pub fn brand_string(&self) -> Option<&str> {
+        // The brand index table of leaf 1 is only defined by Intel;
+        // other vendors use those bits for their own brand IDs.
         self.brand_string.as_ref().map(|bs| bs as &str).or({
-            self.version_information.and_then(|vi| vi.brand_string())
+            if self.vendor() == Vendor::Intel {
+                self.version_information.and_then(|vi| vi.brand_string())
+            } else {
+                None
+            }
         })
     }
-
-    delegate_flag!(version_information, {
-        sse3,
-        pclmulqdq,
-        dtes64,
-        monitor,
-        ds_cpl,
-        vmx,
-        smx,
-        eist,
-        tm2,
-        ssse3,
-        cnxt_id,
-        sdbg,
-        fma,
-        cmpxchg16b,
-        xtpr_update_control,
-        pdcm,
-        pcid,
-        dca,
-        sse4_1,
-        sse4_2,
-        x2apic,
-        movbe,
-        popcnt,
-        tsc_deadline,
-        aesni,
-        xsave,
-        osxsave,
-        avx,
-        f16c,
-        rdrand,
-        fpu,
-        vme,
-        de,
-        pse,
-        tsc,
-        msr,
-        pae,
-        mce,
-        cx8,
-        apic,
-        sep,
-        mtrr,
-        pge,
-        mca,
-        cmov,
-        pat,
-        pse_36,
-        psn,
-        clfsh,
-        ds,
-        acpi,
-        mmx,
-        fxsr,
-        sse,
-        sse2,
-        ss,
-        htt,
-        tm,
-        pbe
-    });
-
-    delegate_flag!(thermal_power_management_information, {
-        digital_temperature_sensor,
-        intel_turbo_boost,
-        arat,
-        pln,
-        ecmd,
-        ptm,
-        hwp,
-        hwp_notification,
-        hwp_activity_window,
-        hwp_energy_performance_preference,
-        hdc,
-        hardware_coordination_feedback,
-        performance_energy_bias
-    });
-
-    delegate_flag!(structured_extended_information, {
-        fsgsbase,
-        ia32_tsc_adjust_msr,
-        bmi1,
-        hle,
-        avx2,
-        smep,
-        bmi2,
-        enhanced_rep_movsb_stosb,
-        invpcid,
-        rtm,
-        pqm,
-        deprecates_fpu_cs_ds,
-        pqe,
-        rdseed,
-        adx,
-        smap,
-        intel_processor_trace,
-        prefetchwt1
-    });
-
-    delegate_flag!(extended_processor_signature, {
-        lahf_sahf_in_64_bit,
-        lzcnt,
-        prefetchw,
-        syscall_sysret_in_64_bit,
-        execute_disable,
-        gigabyte_pages,
-        rdtscp_and_ia32_tsc_aux,
-        intel_64_bit_architecture
-    });
-
-    delegate_flag!(time_stamp_counter, {
-        invariant_tsc
-    });
 }
 
 cfg_if! {
     if #[cfg(any(target_arch = "x86_64", target_arch = "x86"))] {
 
         #[test]
-        fn basic_genuine_intel() {
-            let (_, b, c, d) = cpuid(RequestType::BasicInformation);
+        fn basic_vendor_string() {
+            let (_, b, c, d) = cpuid(&Native, RequestType::BasicInformation);
+            let vendor_string = master().unwrap().vendor_string();
+
+            assert_eq!(&vendor_string[0..4], as_bytes(&b));
+            assert_eq!(&vendor_string[4..8], as_bytes(&d));
+            assert_eq!(&vendor_string[8..12], as_bytes(&c));
+
+            if master().unwrap().vendor() == Vendor::Intel {
+                assert_eq!(b"Genu", as_bytes(&b));
+                assert_eq!(b"ntel", as_bytes(&c));
+                assert_eq!(b"ineI", as_bytes(&d));
+            }
+        }
+
+        #[test]
+        fn cpuid_is_available() {
+            assert!(is_cpuid_available());
+            assert!(master().is_some());
+        }
 
-            assert_eq!(b"Genu", as_bytes(&b));
-            assert_eq!(b"ntel", as_bytes(&c));
-            assert_eq!(b"ineI", as_bytes(&d));
+        #[test]
+        fn query_honors_subleaf() {
+            let max_value = query(RequestType::BasicInformation as u32, 0).eax;
+            if max_value < 4 { return }
+
+            // Deterministic cache parameters; each subleaf is a different cache
+            let l1 = query(4, 0);
+            let l1_again = query(4, 0);
+            let next = query(4, 1);
+
+            assert_eq!(l1, l1_again);
+            if bits_of(l1.eax, 0, 4) != 0 {
+                assert!(l1 != next);
+            }
         }
 
         #[test]
         fn brand_string_contains_intel() {
-            assert!(master().unwrap().brand_string().unwrap().contains("Intel(R)"))
+            let master = master().unwrap();
+            if master.vendor() != Vendor::Intel { return }
+
+            assert!(master.brand_string().unwrap().contains("Intel(R)"))
         }
 
     } else {}
 }
+
+#[cfg(test)]
+fn haswell_source(leaf: u32, _subleaf: u32) -> CpuidResult {
+    fn regs(eax: u32, ebx: u32, ecx: u32, edx: u32) -> CpuidResult {
+        CpuidResult { eax, ebx, ecx, edx }
+    }
+
+    match leaf {
+        0x00000000 => regs(0x0000000D, 0x756E6547, 0x6C65746E, 0x49656E69),
+        0x00000001 => regs(0x000306C3, 0x00100800, 0x7FFAFBFF, 0xBFEBFBFF),
+        0x00000006 => regs(0x00000077, 0x00000002, 0x00000009, 0x00000000),
+        0x00000007 => regs(0x00000000, 0x000027AB, 0x00000000, 0x00000000),
+        0x80000000 => regs(0x80000008, 0x00000000, 0x00000000, 0x00000000),
+        0x80000001 => regs(0x00000000, 0x00000000, 0x00000021, 0x2C100800),
+        0x80000002 => regs(0x65746E49, 0x2952286C, 0x726F4320, 0x4D542865),
+        0x80000003 => regs(0x37692029, 0x3737342D, 0x50432030, 0x20402055),
+        0x80000004 => regs(0x30342E33, 0x007A4847, 0x00000000, 0x00000000),
+        0x80000006 => regs(0x00000000, 0x00000000, 0x01006040, 0x00000000),
+        0x80000007 => regs(0x00000000, 0x00000000, 0x00000000, 0x00000100),
+        0x80000008 => regs(0x00003027, 0x00000000, 0x00000000, 0x00000000),
+        _ => CpuidResult::default(),
+    }
+}
+
+#[test]
+fn master_from_source_decodes_every_leaf() {
+    let master = Master::from_source(&haswell_source);
+
+    let vi = master.version_information().unwrap();
+    assert_eq!(vi.family_id(), 0x06);
+    assert_eq!(vi.model_id(), 0x3C);
+    assert_eq!(vi.stepping(), 3);
+
+    assert!(master.sse4_2());
+    assert!(master.avx2());
+    assert!(master.lzcnt());
+    assert!(master.invariant_tsc());
+    assert!(!master.prefetchwt1());
+    assert_eq!(master.brand_string(), Some("Intel(R) Core(TM) i7-4770 CPU @ 3.40GHz"));
+    assert_eq!(master.cache_line().unwrap().cache_size(), 256);
+    assert_eq!(master.physical_address_size().unwrap().physical_address_bits(), 39);
+    assert_eq!(&master.vendor_string(), b"GenuineIntel");
+    assert_eq!(master.vendor(), Vendor::Intel);
+}
+
+#[test]
+fn brand_string_stops_at_invalid_utf8() {
+    let source = |leaf, subleaf| {
+        match leaf {
+            0x80000002 => CpuidResult { ebx: 0xFFFFFFFF, ..haswell_source(leaf, subleaf) },
+            _ => haswell_source(leaf, subleaf),
+        }
+    };
+    let master = Master::from_source(&source);
+
+    assert_eq!(master.brand_string(), Some("Inte"));
+}
+
+#[test]
+fn brand_index_is_intel_specific() {
+    // An Athlon 64 with an 8-bit brand ID but no brand string leaves
+    let source = |leaf, _subleaf| {
+        let (eax, ebx, ecx, edx) = match leaf {
+            0x00000000 => (0x00000001, 0x68747541, 0x444D4163, 0x69746E65),
+            0x00000001 => (0x00000F48, 0x00000802, 0x00000000, 0x078BFBFF),
+            _ => (0, 0, 0, 0),
+        };
+        CpuidResult { eax, ebx, ecx, edx }
+    };
+    let master = Master::from_source(&source);
+
+    assert_eq!(master.vendor(), Vendor::Amd);
+    assert!(master.version_information().unwrap().brand_string().is_some());
+    assert_eq!(master.brand_string(), None);
+}
+
+#[test]
+fn master_from_source_respects_maximum_leaf() {
+    let source = |leaf, subleaf| {
+        match leaf {
+            0x00000000 => CpuidResult { eax: 0x00000001, ..haswell_source(leaf, subleaf) },
+            0x80000000 => CpuidResult { eax: 0x80000004, ..haswell_source(leaf, subleaf) },
+            _ => haswell_source(leaf, subleaf),
+        }
+    };
+    let master = Master::from_source(&source);
+
+    assert!(master.version_information().is_some());
+    assert!(master.structured_extended_information().is_none());
+    assert!(master.brand_string().is_some());
+    assert!(master.cache_line().is_none());
+    assert!(!master.avx2());
+}
+
+#[test]
+fn builder_queries_only_selected_groups() {
+    use core::cell::RefCell;
+
+    let executed = RefCell::new(Vec::new());
+    let source = |leaf, subleaf| {
+        executed.borrow_mut().push(leaf);
+        haswell_source(leaf, subleaf)
+    };
+
+    let mut master = MasterBuilder::new().with(LeafGroup::BrandString).build_from_source(&source);
+    assert_eq!(*executed.borrow(), [0x80000000, 0x80000002, 0x80000003, 0x80000004]);
+    assert!(master.brand_string().is_some());
+    assert!(master.version_information().is_none());
+    assert_eq!(master.vendor(), Vendor::Unknown([0; 12]));
+
+    executed.borrow_mut().clear();
+    master.fetch(&source, LeafGroup::VersionInformation);
+    master.fetch(&source, LeafGroup::BrandString);
+    assert_eq!(*executed.borrow(), [0x00000000, 0x00000001]);
+    assert_eq!(master.vendor(), Vendor::Intel);
+    assert!(master.version_information().is_some());
+}
+
+#[test]
+fn missing_leaves_are_explained() {
+    let source = |leaf, subleaf| {
+        match leaf {
+            0x80000000 => CpuidResult { eax: 0x80000004, ..haswell_source(leaf, subleaf) },
+            _ => haswell_source(leaf, subleaf),
+        }
+    };
+    let master = MasterBuilder::all()
+        .without(LeafGroup::StructuredExtendedInformation)
+        .build_from_source(&source);
+
+    assert!(master.try_version_information().is_ok());
+    assert!(master.try_brand_string().is_ok());
+    match master.try_cache_line() {
+        Err(Error::LeafNotSupported { leaf: 0x80000006, max: 0x80000004 }) => {}
+        other => panic!("{:?}", other),
+    }
+    match master.try_structured_extended_information() {
+        Err(Error::NotQueried(LeafGroup::StructuredExtendedInformation)) => {}
+        other => panic!("{:?}", other),
+    }
+
+    let master = MasterBuilder::new().build_from_source(&source);
+    assert!(matches!(master.try_vendor(), Err(Error::NotQueried(LeafGroup::BasicInformation))));
+}
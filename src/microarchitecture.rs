@@ -0,0 +1,377 @@
+//! The microarchitecture of a processor, identified from its vendor,
+//! family, model and stepping.
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Master, Vendor};
+
+/// A processor microarchitecture.
+///
+/// Server variants with their own instruction set, such as Skylake-SP,
+/// and refreshes that are told apart only by the stepping, such as
+/// Cascade Lake, are listed separately.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Microarchitecture {
+    // Intel NetBurst and Core
+    NetBurst,
+    Core,
+    Penryn,
+    Nehalem,
+    Westmere,
+    SandyBridge,
+    IvyBridge,
+    Haswell,
+    Broadwell,
+    Skylake,
+    SkylakeSp,
+    CascadeLake,
+    CooperLake,
+    KabyLake,
+    CoffeeLake,
+    WhiskeyLake,
+    CometLake,
+    CannonLake,
+    IceLake,
+    IceLakeSp,
+    TigerLake,
+    RocketLake,
+    AlderLake,
+    RaptorLake,
+    MeteorLake,
+    ArrowLake,
+    LunarLake,
+    PantherLake,
+    SapphireRapids,
+    EmeraldRapids,
+    GraniteRapids,
+
+    // Intel Atom
+    Bonnell,
+    Saltwell,
+    Silvermont,
+    Airmont,
+    Goldmont,
+    GoldmontPlus,
+    Tremont,
+    Gracemont,
+    SierraForest,
+    GrandRidge,
+    ClearwaterForest,
+
+    // Intel Xeon Phi
+    KnightsLanding,
+    KnightsMill,
+
+    // AMD
+    K8,
+    K10,
+    Bobcat,
+    Bulldozer,
+    Piledriver,
+    Steamroller,
+    Excavator,
+    Jaguar,
+    Puma,
+    Zen,
+    ZenPlus,
+    Zen2,
+    Zen3,
+    Zen3Plus,
+    Zen4,
+    Zen5,
+
+    // Hygon
+    Dhyana,
+
+    // Zhaoxin
+    Lujiazui,
+    Yongfeng,
+    Shijidadao,
+}
+
+impl Microarchitecture {
+    /// Identifies the microarchitecture from the family, model and
+    /// stepping of a `ProcessorSignature`.
+    pub fn identify(vendor: &Vendor, family: u32, model: u32, stepping: u32)
+                    -> Option<Microarchitecture>
+    {
+        use self::Microarchitecture::*;
+
+        let uarch = match (vendor, family) {
+            (&Vendor::Intel, 0x06) => match model {
+                0x0F | 0x16 => Core,
+                0x17 | 0x1D => Penryn,
+                0x1A | 0x1E | 0x1F | 0x2E => Nehalem,
+                0x25 | 0x2C | 0x2F => Westmere,
+                0x2A | 0x2D => SandyBridge,
+                0x3A | 0x3E => IvyBridge,
+                0x3C | 0x3F | 0x45 | 0x46 => Haswell,
+                0x3D | 0x47 | 0x4F | 0x56 => Broadwell,
+                0x4E | 0x5E => Skylake,
+                0x55 if stepping >= 10 => CooperLake,
+                0x55 if stepping >= 5 => CascadeLake,
+                0x55 => SkylakeSp,
+                // Kaby Lake R shares stepping 10 with Coffee Lake U
+                0x8E if stepping <= 10 => KabyLake,
+                0x8E if stepping == 11 => WhiskeyLake,
+                0x8E => CometLake,
+                0x9E if stepping <= 9 => KabyLake,
+                0x9E => CoffeeLake,
+                0xA5 | 0xA6 => CometLake,
+                0x66 => CannonLake,
+                0x7D | 0x7E | 0x9D => IceLake,
+                0x6A | 0x6C => IceLakeSp,
+                0x8C | 0x8D => TigerLake,
+                0xA7 => RocketLake,
+                0x97 | 0x9A => AlderLake,
+                0xB7 | 0xBA | 0xBF => RaptorLake,
+                0xAA | 0xAC => MeteorLake,
+                0xB5 | 0xC5 | 0xC6 => ArrowLake,
+                0xBD => LunarLake,
+                0xCC => PantherLake,
+                0x8F => SapphireRapids,
+                0xCF => EmeraldRapids,
+                0xAD | 0xAE => GraniteRapids,
+                0x1C | 0x26 => Bonnell,
+                0x27 | 0x35 | 0x36 => Saltwell,
+                0x37 | 0x4A | 0x4D | 0x5A | 0x5D => Silvermont,
+                0x4C | 0x75 => Airmont,
+                0x5C | 0x5F => Goldmont,
+                0x7A => GoldmontPlus,
+                0x86 | 0x8A | 0x96 | 0x9C => Tremont,
+                0xBE => Gracemont,
+                0xAF => SierraForest,
+                0xB6 => GrandRidge,
+                0xDD => ClearwaterForest,
+                0x57 => KnightsLanding,
+                0x85 => KnightsMill,
+                _ => return None,
+            },
+            (&Vendor::Intel, 0x0F) => NetBurst,
+            (&Vendor::Amd, 0x0F) | (&Vendor::Amd, 0x11) => K8,
+            (&Vendor::Amd, 0x10) | (&Vendor::Amd, 0x12) => K10,
+            (&Vendor::Amd, 0x14) => Bobcat,
+            (&Vendor::Amd, 0x15) => match model {
+                0x02 | 0x10..=0x1F => Piledriver,
+                0x00..=0x0F => Bulldozer,
+                0x30..=0x3F => Steamroller,
+                0x60..=0x7F => Excavator,
+                _ => return None,
+            },
+            (&Vendor::Amd, 0x16) => match model {
+                0x30..=0x3F => Puma,
+                _ => Jaguar,
+            },
+            (&Vendor::Amd, 0x17) => match model {
+                0x08 | 0x18 => ZenPlus,
+                0x00..=0x2F => Zen,
+                _ => Zen2,
+            },
+            (&Vendor::Amd, 0x19) => match model {
+                0x10..=0x1F | 0x60..=0x7F | 0xA0..=0xAF => Zen4,
+                0x40..=0x4F => Zen3Plus,
+                _ => Zen3,
+            },
+            (&Vendor::Amd, 0x1A) => Zen5,
+            (&Vendor::Hygon, 0x18) => Dhyana,
+            (&Vendor::Zhaoxin, 0x07) | (&Vendor::Centaur, 0x07) => match model {
+                0x1B => Lujiazui,
+                0x3B => Yongfeng,
+                0x5B => Shijidadao,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(uarch)
+    }
+
+    /// The codename, e.g. `Cascade Lake` or `Zen 3`.
+    pub fn codename(self) -> &'static str {
+        use self::Microarchitecture::*;
+
+        match self {
+            NetBurst => "NetBurst",
+            Core => "Core",
+            Penryn => "Penryn",
+            Nehalem => "Nehalem",
+            Westmere => "Westmere",
+            SandyBridge => "Sandy Bridge",
+            IvyBridge => "Ivy Bridge",
+            Haswell => "Haswell",
+            Broadwell => "Broadwell",
+            Skylake => "Skylake",
+            SkylakeSp => "Skylake-SP",
+            CascadeLake => "Cascade Lake",
+            CooperLake => "Cooper Lake",
+            KabyLake => "Kaby Lake",
+            CoffeeLake => "Coffee Lake",
+            WhiskeyLake => "Whiskey Lake",
+            CometLake => "Comet Lake",
+            CannonLake => "Cannon Lake",
+            IceLake => "Ice Lake",
+            IceLakeSp => "Ice Lake-SP",
+            TigerLake => "Tiger Lake",
+            RocketLake => "Rocket Lake",
+            AlderLake => "Alder Lake",
+            RaptorLake => "Raptor Lake",
+            MeteorLake => "Meteor Lake",
+            ArrowLake => "Arrow Lake",
+            LunarLake => "Lunar Lake",
+            PantherLake => "Panther Lake",
+            SapphireRapids => "Sapphire Rapids",
+            EmeraldRapids => "Emerald Rapids",
+            GraniteRapids => "Granite Rapids",
+            Bonnell => "Bonnell",
+            Saltwell => "Saltwell",
+            Silvermont => "Silvermont",
+            Airmont => "Airmont",
+            Goldmont => "Goldmont",
+            GoldmontPlus => "Goldmont Plus",
+            Tremont => "Tremont",
+            Gracemont => "Gracemont",
+            SierraForest => "Sierra Forest",
+            GrandRidge => "Grand Ridge",
+            ClearwaterForest => "Clearwater Forest",
+            KnightsLanding => "Knights Landing",
+            KnightsMill => "Knights Mill",
+            K8 => "K8",
+            K10 => "K10",
+            Bobcat => "Bobcat",
+            Bulldozer => "Bulldozer",
+            Piledriver => "Piledriver",
+            Steamroller => "Steamroller",
+            Excavator => "Excavator",
+            Jaguar => "Jaguar",
+            Puma => "Puma",
+            Zen => "Zen",
+            ZenPlus => "Zen+",
+            Zen2 => "Zen 2",
+            Zen3 => "Zen 3",
+            Zen3Plus => "Zen 3+",
+            Zen4 => "Zen 4",
+            Zen5 => "Zen 5",
+            Dhyana => "Dhyana",
+            Lujiazui => "Lujiazui",
+            Yongfeng => "Yongfeng",
+            Shijidadao => "Shijidadao",
+        }
+    }
+
+    /// The main products built on the microarchitecture, e.g.
+    /// `2nd generation Xeon Scalable`.
+    pub fn platform(self) -> &'static str {
+        use self::Microarchitecture::*;
+
+        match self {
+            NetBurst => "Pentium 4, Pentium D",
+            Core => "Core 2 (65 nm)",
+            Penryn => "Core 2 (45 nm)",
+            Nehalem => "1st generation Core, Xeon 5500",
+            Westmere => "1st generation Core, Xeon 5600",
+            SandyBridge => "2nd generation Core, Xeon E5",
+            IvyBridge => "3rd generation Core, Xeon E5 v2",
+            Haswell => "4th generation Core, Xeon E5 v3",
+            Broadwell => "5th generation Core, Xeon E5 v4",
+            Skylake => "6th generation Core",
+            SkylakeSp => "1st generation Xeon Scalable",
+            CascadeLake => "2nd generation Xeon Scalable",
+            CooperLake => "3rd generation Xeon Scalable",
+            KabyLake => "7th and 8th generation Core",
+            CoffeeLake => "8th and 9th generation Core",
+            WhiskeyLake => "8th generation Core",
+            CometLake => "10th generation Core",
+            CannonLake => "8th generation Core",
+            IceLake => "10th generation Core",
+            IceLakeSp => "3rd generation Xeon Scalable",
+            TigerLake => "11th generation Core",
+            RocketLake => "11th generation Core",
+            AlderLake => "12th generation Core",
+            RaptorLake => "13th and 14th generation Core",
+            MeteorLake => "Core Ultra Series 1",
+            ArrowLake => "Core Ultra Series 2",
+            LunarLake => "Core Ultra Series 2",
+            PantherLake => "Core Ultra Series 3",
+            SapphireRapids => "4th generation Xeon Scalable",
+            EmeraldRapids => "5th generation Xeon Scalable",
+            GraniteRapids => "Xeon 6 with P-cores",
+            Bonnell | Saltwell => "Atom",
+            Silvermont | Airmont => "Atom, Celeron, Pentium",
+            Goldmont => "Atom, Celeron, Pentium",
+            GoldmontPlus => "Pentium Silver, Celeron",
+            Tremont => "Atom, Pentium Silver, Celeron",
+            Gracemont => "Intel Processor N-series, Core i3 N-series",
+            SierraForest => "Xeon 6 with E-cores",
+            GrandRidge => "Atom P5000",
+            ClearwaterForest => "Xeon 6+ with E-cores",
+            KnightsLanding => "Xeon Phi x200",
+            KnightsMill => "Xeon Phi 72x5",
+            K8 => "Athlon 64, Opteron",
+            K10 => "Phenom, Opteron",
+            Bobcat => "E-Series, C-Series",
+            Bulldozer => "FX, Opteron 6200",
+            Piledriver => "FX, A-Series, Opteron 6300",
+            Steamroller => "A-Series",
+            Excavator => "A-Series",
+            Jaguar => "Athlon, Sempron, Opteron X",
+            Puma => "A-Series",
+            Zen => "Ryzen 1000, EPYC 7001",
+            ZenPlus => "Ryzen 2000",
+            Zen2 => "Ryzen 3000 and 4000, EPYC 7002",
+            Zen3 => "Ryzen 5000, EPYC 7003",
+            Zen3Plus => "Ryzen 6000",
+            Zen4 => "Ryzen 7000 and 8000, EPYC 9004",
+            Zen5 => "Ryzen 9000, EPYC 9005",
+            Dhyana => "Hygon C86",
+            Lujiazui => "KaiXian KX-6000, KaiSheng KH-30000",
+            Yongfeng => "KaiXian KX-7000",
+            Shijidadao => "KaiSheng KH-50000",
+        }
+    }
+}
+
+impl fmt::Display for Microarchitecture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.codename())
+    }
+}
+
+impl Master {
+    /// The microarchitecture of the processor, or `None` if leaf 1 was
+    /// not queried or the processor is not known to this crate.
+    pub fn microarchitecture(&self) -> Option<Microarchitecture> {
+        let signature = self.processor_signature()?;
+        Microarchitecture::identify(&self.vendor(), signature.family(), signature.model(),
+                                    signature.stepping())
+    }
+}
+
+#[test]
+fn identifies_by_signature() {
+    use self::Microarchitecture::*;
+
+    let intel = |model, stepping| Microarchitecture::identify(&Vendor::Intel, 6, model, stepping);
+    assert_eq!(intel(0x55, 4), Some(SkylakeSp));
+    assert_eq!(intel(0x55, 7), Some(CascadeLake));
+    assert_eq!(intel(0x55, 11), Some(CooperLake));
+    assert_eq!(intel(0x9E, 9), Some(KabyLake));
+    assert_eq!(intel(0x9E, 13), Some(CoffeeLake));
+    assert_eq!(intel(0x8F, 8), Some(SapphireRapids));
+    assert_eq!(intel(0xFF, 0), None);
+
+    let amd = |family, model| Microarchitecture::identify(&Vendor::Amd, family, model, 0);
+    assert_eq!(amd(0x15, 0x02), Some(Piledriver));
+    assert_eq!(amd(0x17, 0x08), Some(ZenPlus));
+    assert_eq!(amd(0x17, 0x71), Some(Zen2));
+    assert_eq!(amd(0x19, 0x21), Some(Zen3));
+    assert_eq!(amd(0x19, 0x44), Some(Zen3Plus));
+    assert_eq!(amd(0x19, 0x61), Some(Zen4));
+    assert_eq!(Zen4.codename(), "Zen 4");
+
+    let master = Master::from_source(&::haswell_source);
+    assert_eq!(master.microarchitecture(), Some(Haswell));
+    assert_eq!(Haswell.platform(), "4th generation Core, Xeon E5 v3");
+}
@@ -0,0 +1,125 @@
+//! CPU information of every logical CPU, gathered by moving the
+//! calling thread from CPU to CPU.
+//!
+//! Only Linux is supported, where the thread is moved with
+//! `sched_setaffinity`.
+
+use std::{io, mem};
+
+use libc::{self, c_ulong, cpu_set_t};
+
+use super::{Error, Master};
+
+// A set of CPUs of any size. `cpu_set_t` is limited to `CPU_SETSIZE`
+// (1024) CPUs, so the set is allocated the way `CPU_ALLOC` does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CpuSet(Vec<c_ulong>);
+
+impl CpuSet {
+    const BITS: usize = mem::size_of::<c_ulong>() * 8;
+
+    fn with_words(words: usize) -> CpuSet {
+        CpuSet(vec![0; words])
+    }
+
+    fn size(&self) -> usize {
+        self.0.len() * mem::size_of::<c_ulong>()
+    }
+
+    fn contains(&self, cpu: usize) -> bool {
+        self.0.get(cpu / CpuSet::BITS).is_some_and(|word| word & (1 << (cpu % CpuSet::BITS)) != 0)
+    }
+
+    fn only(&self, cpu: usize) -> CpuSet {
+        let mut set = CpuSet::with_words(self.0.len());
+        set.0[cpu / CpuSet::BITS] = 1 << (cpu % CpuSet::BITS);
+        set
+    }
+
+    fn cpus<'a>(&'a self) -> impl Iterator<Item = usize> + 'a {
+        (0..self.0.len() * CpuSet::BITS).filter(move |&cpu| self.contains(cpu))
+    }
+}
+
+fn current_affinity() -> io::Result<CpuSet> {
+    let mut words = libc::CPU_SETSIZE as usize / CpuSet::BITS;
+    loop {
+        let mut set = CpuSet::with_words(words);
+        let ptr = set.0.as_mut_ptr() as *mut cpu_set_t;
+        if unsafe { libc::sched_getaffinity(0, set.size(), ptr) } == 0 {
+            return Ok(set);
+        }
+
+        // The kernel's mask is larger than the set
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EINVAL) || words >= 1 << 16 {
+            return Err(e);
+        }
+        words *= 2;
+    }
+}
+
+fn set_affinity(set: &CpuSet) -> io::Result<()> {
+    let ptr = set.0.as_ptr() as *const cpu_set_t;
+    if unsafe { libc::sched_setaffinity(0, set.size(), ptr) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Restores the affinity the thread had before it started moving.
+struct RestoreAffinity(CpuSet);
+
+impl Drop for RestoreAffinity {
+    fn drop(&mut self) {
+        let _ = set_affinity(&self.0);
+    }
+}
+
+/// The CPU information of each logical CPU in the affinity mask of the
+/// calling thread, along with the number of that CPU.
+///
+/// The thread is pinned to each CPU in turn while CPUID is executed,
+/// so per-core values such as the APIC ID are reported correctly
+/// without needing the `cpuid` driver or root privileges. The original
+/// affinity is restored before returning, even on failure.
+///
+/// Only available on Linux. There is no limit on the number of CPUs.
+pub fn per_cpu() -> Result<Vec<(usize, Master)>, Error> {
+    if !::is_cpuid_available() {
+        return Err(Error::CpuidUnavailable);
+    }
+
+    let original = current_affinity()?;
+    let restore = RestoreAffinity(original);
+
+    let mut masters = Vec::new();
+
+    for cpu in restore.0.cpus() {
+        set_affinity(&restore.0.only(cpu))?;
+        masters.push((cpu, Master::new()));
+    }
+
+    Ok(masters)
+}
+
+#[test]
+fn visits_each_cpu_and_restores_affinity() {
+    let before = current_affinity().unwrap();
+    let masters = per_cpu().unwrap();
+    let after = current_affinity().unwrap();
+
+    assert!(!masters.is_empty());
+    assert!(masters.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(before, after);
+    assert!(masters.iter().all(|&(cpu, _)| before.contains(cpu)));
+}
+
+#[test]
+fn cpu_sets_beyond_cpu_setsize() {
+    let set = CpuSet::with_words(32).only(1500);
+    assert!(set.contains(1500));
+    assert!(!set.contains(1499));
+    assert!(!set.contains(1 << 20));
+    assert_eq!(set.cpus().collect::<Vec<_>>(), [1500]);
+}
@@ -0,0 +1,140 @@
+//! The names rustc uses for target features.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{Feature, FeatureSet, Master};
+
+impl Feature {
+    /// The name of the feature in rustc's `target_feature`, e.g. `sse4.2`.
+    ///
+    /// Returns `None` for features without a stable rustc equivalent.
+    pub fn rustc_name(self) -> Option<&'static str> {
+        let name = match self {
+            Feature::Fxsr => "fxsr",
+            Feature::Sse => "sse",
+            Feature::Sse2 => "sse2",
+            Feature::Sse3 => "sse3",
+            Feature::Ssse3 => "ssse3",
+            Feature::Sse4_1 => "sse4.1",
+            Feature::Sse4_2 => "sse4.2",
+            Feature::Sse4a => "sse4a",
+            Feature::Pclmulqdq => "pclmulqdq",
+            Feature::Cmpxchg16b => "cmpxchg16b",
+            Feature::Movbe => "movbe",
+            Feature::Popcnt => "popcnt",
+            Feature::Aesni => "aes",
+            Feature::Rdrand => "rdrand",
+            Feature::Rdseed => "rdseed",
+            Feature::Adx => "adx",
+            Feature::Bmi1 => "bmi1",
+            Feature::Bmi2 => "bmi2",
+            Feature::Lzcnt => "lzcnt",
+            Feature::Tbm => "tbm",
+            Feature::Sha => "sha",
+            Feature::Gfni => "gfni",
+            Feature::Xsave => "xsave",
+
+            Feature::Avx => "avx",
+            Feature::Avx2 => "avx2",
+            Feature::Fma => "fma",
+            Feature::F16c => "f16c",
+            Feature::Vaes => "vaes",
+            Feature::Vpclmulqdq => "vpclmulqdq",
+
+            Feature::Avx512f => "avx512f",
+            Feature::Avx512dq => "avx512dq",
+            Feature::Avx512ifma => "avx512ifma",
+            Feature::Avx512cd => "avx512cd",
+            Feature::Avx512bw => "avx512bw",
+            Feature::Avx512vl => "avx512vl",
+            Feature::Avx512vbmi => "avx512vbmi",
+            Feature::Avx512vbmi2 => "avx512vbmi2",
+            Feature::Avx512vnni => "avx512vnni",
+            Feature::Avx512bitalg => "avx512bitalg",
+            Feature::Avx512vpopcntdq => "avx512vpopcntdq",
+            Feature::Avx512Vp2intersect => "avx512vp2intersect",
+            Feature::Avx512Fp16 => "avx512fp16",
+
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl Master {
+    /// The stable rustc target features supported by the processor,
+    /// such as `sse4.2` and `avx2`.
+    ///
+    /// Like `usable_features`, features that need register state the
+    /// OS does not save, such as AVX without the YMM state enabled in
+    /// XCR0, are left out.
+    #[cfg(feature = "alloc")]
+    pub fn rustc_target_features(&self) -> Vec<&'static str> {
+        self.usable_features().iter().filter_map(Feature::rustc_name).collect()
+    }
+
+    /// Displays `rustc_target_features` as the value of
+    /// `-C target-feature`, e.g. `+sse3,+ssse3`.
+    ///
+    /// ```
+    /// if let Some(information) = cupid::master() {
+    ///     println!("-C target-feature={}", information.display_rustc_target_features());
+    /// }
+    /// ```
+    pub fn display_rustc_target_features(&self) -> RustcTargetFeatures {
+        RustcTargetFeatures(self.usable_features())
+    }
+}
+
+/// Displays the rustc target features of a set as `+a,+b`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustcTargetFeatures(FeatureSet);
+
+impl fmt::Display for RustcTargetFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, name) in self.0.iter().filter_map(Feature::rustc_name).enumerate() {
+            if i != 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "+{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn avx_requires_os_support() {
+    use super::CpuidResult;
+
+    struct Source(u64);
+
+    impl ::CpuidSource for Source {
+        fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuidResult {
+            ::haswell_source(leaf, subleaf)
+        }
+
+        fn xcr0(&self) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    let enabled = Master::from_source(&Source(0b111));
+    let features = enabled.rustc_target_features();
+    assert!(features.contains(&"sse4.2"));
+    assert!(features.contains(&"avx2"));
+    assert!(features.contains(&"fma"));
+    assert!(!features.contains(&"avx512f"));
+    assert!(enabled.display_rustc_target_features().to_string().starts_with("+sse3,+pclmulqdq,"));
+
+    // Only the x87 and SSE state is saved
+    let disabled = Master::from_source(&Source(0b011));
+    let features = disabled.rustc_target_features();
+    assert!(features.contains(&"sse4.2"));
+    assert!(features.contains(&"bmi2"));
+    assert!(!features.contains(&"avx"));
+    assert!(!features.contains(&"avx2"));
+    assert!(!features.contains(&"fma"));
+}
@@ -0,0 +1,242 @@
+//! Serde support for the decoded CPU information.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::str;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use serde::ser::SerializeStruct;
+
+use super::{BrandString, BRAND_STRING_LENGTH, CpuidResult, Master, ProcessorSignature};
+#[cfg(feature = "alloc")]
+use super::{RawDump, RawLeaf};
+
+/// Serializes the decoded values alongside the raw registers.
+///
+/// Plain serialization only records the registers, which is all that
+/// is needed to answer every query. Wrapping a leaf struct or `Master`
+/// in `Decoded` additionally writes every decoded field, which is
+/// useful for human readers and other tools. The decoded output can
+/// be deserialized as the wrapped type; the decoded fields are ignored.
+#[derive(Debug, Copy, Clone)]
+pub struct Decoded<'a, T: 'a>(pub &'a T);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "BrandString")]
+struct RawBrandString {
+    registers: [CpuidResult; 3],
+}
+
+fn brand_string_registers(bs: &BrandString) -> [CpuidResult; 3] {
+    [bs.registers(0), bs.registers(1), bs.registers(2)]
+}
+
+impl Serialize for BrandString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        RawBrandString { registers: brand_string_registers(self) }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BrandString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let raw = RawBrandString::deserialize(deserializer)?;
+
+        let mut bytes = [0; BRAND_STRING_LENGTH];
+        for (part, r) in raw.registers.iter().enumerate() {
+            for (idx, register) in [r.eax, r.ebx, r.ecx, r.edx].iter().enumerate() {
+                let start = part * 16 + idx * 4;
+                bytes[start..start + 4].copy_from_slice(&register.to_le_bytes());
+            }
+        }
+
+        let nul_terminator = bytes.iter().position(|&b| b == 0).unwrap_or(0);
+        if str::from_utf8(&bytes[..nul_terminator]).is_err() {
+            return Err(D::Error::custom("the brand string is not UTF-8"));
+        }
+
+        Ok(BrandString { bytes })
+    }
+}
+
+impl<'a> Serialize for Decoded<'a, BrandString> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut state = serializer.serialize_struct("BrandString", 2)?;
+        state.serialize_field("registers", &brand_string_registers(self.0))?;
+        state.serialize_field("string", &**self.0)?;
+        state.end()
+    }
+}
+
+// A signature is serialized as its raw value and the decoded family,
+// model and stepping. The vendor rule that extends the model is not
+// recorded; it is restored from the model.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ProcessorSignature")]
+struct RawSignature {
+    eax: u32,
+    family: u32,
+    model: u32,
+    stepping: u32,
+}
+
+impl Serialize for ProcessorSignature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        RawSignature {
+            eax: self.raw(),
+            family: self.family(),
+            model: self.model(),
+            stepping: self.stepping(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProcessorSignature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let raw = RawSignature::deserialize(deserializer)?;
+        ProcessorSignature::with_model(raw.eax, raw.model)
+            .ok_or_else(|| D::Error::custom("model does not match the signature"))
+    }
+}
+
+impl<'a> Serialize for Decoded<'a, Master> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let m = self.0;
+        let mut state = serializer.serialize_struct("Master", 14)?;
+        state.serialize_field("vendor", &m.vendor())?;
+        state.serialize_field("basic_information", &m.basic_information)?;
+        state.serialize_field("extended_function_information", &m.extended_function_information)?;
+        state.serialize_field("version_information",
+                              &m.version_information.as_ref().map(Decoded))?;
+        state.serialize_field("thermal_power_management_information",
+                              &m.thermal_power_management_information.as_ref().map(Decoded))?;
+        state.serialize_field("structured_extended_information",
+                              &m.structured_extended_information.as_ref().map(Decoded))?;
+        state.serialize_field("extended_processor_signature",
+                              &m.extended_processor_signature.as_ref().map(Decoded))?;
+        state.serialize_field("brand_string", &m.brand_string.as_ref().map(Decoded))?;
+        state.serialize_field("cache_line", &m.cache_line.as_ref().map(Decoded))?;
+        state.serialize_field("time_stamp_counter", &m.time_stamp_counter.as_ref().map(Decoded))?;
+        state.serialize_field("physical_address_size",
+                              &m.physical_address_size.as_ref().map(Decoded))?;
+        state.serialize_field("hypervisor", &m.hypervisor())?;
+        state.serialize_field("hypervisor_information", &m.hypervisor_information)?;
+        state.serialize_field("xcr0", &m.xcr0)?;
+        state.end()
+    }
+}
+
+// A dump is serialized as its list of leaves; deserializing restores
+// the ordering that lookups rely on.
+#[cfg(feature = "alloc")]
+impl Serialize for RawDump {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        self.leaves().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de> Deserialize<'de> for RawDump {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let leaves = Vec::<RawLeaf>::deserialize(deserializer)?;
+
+        let mut dump = RawDump::empty();
+        for l in leaves {
+            dump.insert(l.leaf, l.subleaf, l.registers);
+        }
+        Ok(dump)
+    }
+}
+
+#[cfg(test)]
+use serde_json;
+
+#[test]
+fn master_round_trips_through_registers() {
+    let master = Master::from_source(&::haswell_source);
+
+    let json = serde_json::to_string(&master).unwrap();
+    let restored: Master = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(format!("{:?}", restored), format!("{:?}", master));
+    #[cfg(feature = "alloc")]
+    assert_eq!(restored.raw_dump(), master.raw_dump());
+}
+
+#[test]
+fn decoded_master_contains_fields_and_round_trips() {
+    let master = Master::from_source(&::haswell_source);
+
+    let json = serde_json::to_value(Decoded(&master)).unwrap();
+    assert_eq!(json["version_information"]["model_id"], 0x3C);
+    assert_eq!(json["version_information"]["eax"], 0x000306C3);
+    assert_eq!(json["structured_extended_information"]["avx2"], true);
+    assert_eq!(json["brand_string"]["string"], "Intel(R) Core(TM) i7-4770 CPU @ 3.40GHz");
+
+    let restored: Master = serde_json::from_value(json).unwrap();
+    assert_eq!(format!("{:?}", restored), format!("{:?}", master));
+}
+
+#[test]
+fn processor_signatures_round_trip() {
+    use super::Vendor;
+
+    let zen3 = ProcessorSignature::new(0x00A20F10, &Vendor::Amd);
+    let json = serde_json::to_value(zen3).unwrap();
+    assert_eq!(json, serde_json::json!({
+        "eax": 0x00A20F10, "family": 0x19, "model": 0x21, "stepping": 0,
+    }));
+    let restored: ProcessorSignature = serde_json::from_value(json).unwrap();
+    assert_eq!(restored, zen3);
+
+    // An AMD family 6 processor does not extend its model, unlike Zhaoxin
+    let athlon = ProcessorSignature::new(0x00010681, &Vendor::Amd);
+    let restored: ProcessorSignature =
+        serde_json::from_value(serde_json::to_value(athlon).unwrap()).unwrap();
+    assert_eq!(restored, athlon);
+    assert_eq!(restored.model(), 0x08);
+
+    let invalid = serde_json::json!({ "eax": 0x00A20F10, "family": 0x19, "model": 1, "stepping": 0 });
+    assert!(serde_json::from_value::<ProcessorSignature>(invalid).is_err());
+}
+
+#[test]
+fn brand_strings_must_be_utf8() {
+    let json = r#"{ "registers": [
+        { "eax": 4294967295, "ebx": 0, "ecx": 0, "edx": 0 },
+        { "eax": 0, "ebx": 0, "ecx": 0, "edx": 0 },
+        { "eax": 0, "ebx": 0, "ecx": 0, "edx": 0 }
+    ] }"#;
+    assert!(serde_json::from_str::<BrandString>(json).is_err());
+
+    let master = Master::from_source(&::haswell_source);
+    let json = serde_json::to_string(master.brand_string.as_ref().unwrap()).unwrap();
+    let restored: BrandString = serde_json::from_str(&json).unwrap();
+    assert_eq!(&*restored, "Intel(R) Core(TM) i7-4770 CPU @ 3.40GHz");
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn raw_dump_round_trips() {
+    let dump = RawDump::from_source(&::haswell_source);
+
+    let json = serde_json::to_string(&dump).unwrap();
+    let restored: RawDump = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored, dump);
+}
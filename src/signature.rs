@@ -0,0 +1,285 @@
+//! The processor signature reported in EAX of leaf 1.
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Master, Vendor};
+
+/// When the extended model field extends the model.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum ModelRule {
+    /// Intel: base family 6 or 0xF
+    Intel,
+    /// AMD and Hygon: base family 0xF
+    Amd,
+    /// Every other vendor, as Linux does: base family 6 and above
+    Other,
+}
+
+/// The type of the processor, in bits 12 and 13 of the signature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ProcessorType {
+    /// A processor sold by the OEM
+    Original,
+    /// An Intel OverDrive upgrade processor
+    OverDrive,
+    /// The second processor of a dual processor system
+    DualProcessor,
+    Reserved,
+}
+
+/// The family, model and stepping of a processor.
+///
+/// The displayed family and model combine the base and extended fields
+/// the way the vendor does, so Zen 3 is family 0x19 model 0x21 rather
+/// than model 0x1.
+///
+/// Signatures are displayed in Intel's `DisplayFamily_DisplayModel`
+/// notation, e.g. `06_8FH`, and their raw value with the hexadecimal
+/// formatters, e.g. `{:#010X}` gives `0x000806F8`. Both notations can be
+/// parsed.
+///
+/// Signatures are equal when their raw value and model are, regardless
+/// of the vendor rule that gave the model.
+#[derive(Debug, Copy, Clone)]
+pub struct ProcessorSignature {
+    eax: u32,
+    rule: ModelRule,
+}
+
+impl ProcessorSignature {
+    /// The signature in EAX of leaf 1 of a processor by the given vendor.
+    pub fn new(eax: u32, vendor: &Vendor) -> ProcessorSignature {
+        let rule = match *vendor {
+            Vendor::Intel => ModelRule::Intel,
+            Vendor::Amd | Vendor::Hygon => ModelRule::Amd,
+            _ => ModelRule::Other,
+        };
+        ProcessorSignature { eax, rule }
+    }
+
+    /// The signature from the displayed family and model.
+    ///
+    /// The stepping and processor type are zero. Fails if the model
+    /// cannot be represented for the family.
+    pub fn from_family_model(family: u32, model: u32) -> Option<ProcessorSignature> {
+        let (base_family, extended_family) = match family {
+            0x00..=0x0E => (family, 0),
+            0x0F..=0x10E => (0x0F, family - 0x0F),
+            _ => return None,
+        };
+        if model > 0xFF || (model > 0x0F && base_family < 0x06) {
+            return None;
+        }
+
+        let eax = extended_family << 20 | (model >> 4) << 16 | base_family << 8 | (model & 0x0F) << 4;
+        Some(ProcessorSignature { eax, rule: ModelRule::Other })
+    }
+
+    // The signature whose vendor rule gives the model, used to restore
+    // a serialized signature without its vendor.
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_model(eax: u32, model: u32) -> Option<ProcessorSignature> {
+        [ModelRule::Intel, ModelRule::Amd, ModelRule::Other].iter()
+            .map(|&rule| ProcessorSignature { eax, rule })
+            .find(|signature| signature.model() == model)
+    }
+
+    /// The raw value of EAX of leaf 1
+    pub fn raw(self) -> u32 {
+        self.eax
+    }
+
+    pub fn stepping(self) -> u32 {
+        self.eax & 0x0F
+    }
+
+    pub fn base_model(self) -> u32 {
+        (self.eax >> 4) & 0x0F
+    }
+
+    pub fn base_family(self) -> u32 {
+        (self.eax >> 8) & 0x0F
+    }
+
+    pub fn processor_type(self) -> ProcessorType {
+        match (self.eax >> 12) & 0x03 {
+            0 => ProcessorType::Original,
+            1 => ProcessorType::OverDrive,
+            2 => ProcessorType::DualProcessor,
+            _ => ProcessorType::Reserved,
+        }
+    }
+
+    pub fn extended_model(self) -> u32 {
+        (self.eax >> 16) & 0x0F
+    }
+
+    pub fn extended_family(self) -> u32 {
+        (self.eax >> 20) & 0xFF
+    }
+
+    /// The family, including the extended family when the base family
+    /// is 0xF.
+    pub fn family(self) -> u32 {
+        if self.base_family() == 0x0F {
+            self.base_family() + self.extended_family()
+        } else {
+            self.base_family()
+        }
+    }
+
+    /// The model, including the extended model according to the rule of
+    /// the vendor.
+    pub fn model(self) -> u32 {
+        let base_family = self.base_family();
+        let extended = match self.rule {
+            ModelRule::Intel => base_family == 0x06 || base_family == 0x0F,
+            ModelRule::Amd => base_family == 0x0F,
+            ModelRule::Other => base_family >= 0x06,
+        };
+
+        if extended {
+            self.extended_model() << 4 | self.base_model()
+        } else {
+            self.base_model()
+        }
+    }
+}
+
+impl PartialEq for ProcessorSignature {
+    fn eq(&self, other: &ProcessorSignature) -> bool {
+        self.eax == other.eax && self.model() == other.model()
+    }
+}
+
+impl Eq for ProcessorSignature {}
+
+impl Hash for ProcessorSignature {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.eax.hash(state);
+        self.model().hash(state);
+    }
+}
+
+/// `DisplayFamily_DisplayModel`, e.g. `06_8FH`
+impl fmt::Display for ProcessorSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02X}_{:02X}H", self.family(), self.model())
+    }
+}
+
+impl fmt::UpperHex for ProcessorSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.eax, f)
+    }
+}
+
+impl fmt::LowerHex for ProcessorSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.eax, f)
+    }
+}
+
+/// Parses `06_8FH` (the `H` is optional) or a raw value such as
+/// `0x000806F8`.
+///
+/// As the vendor is not known, the model of a raw value is computed with
+/// the rule most vendors use; see `ProcessorSignature::new`.
+impl FromStr for ProcessorSignature {
+    type Err = ParseSignatureError;
+
+    fn from_str(s: &str) -> Result<ProcessorSignature, ParseSignatureError> {
+        let s = s.trim();
+        let hex = |digits: &str, reason| {
+            if digits.is_empty() || digits.len() > 8 {
+                return Err(ParseSignatureError { reason });
+            }
+            u32::from_str_radix(digits, 16).map_err(|_| ParseSignatureError { reason })
+        };
+
+        if let Some((family, model)) = s.split_once('_') {
+            let model = model.strip_suffix(['H', 'h']).unwrap_or(model);
+            let family = hex(family, "invalid family")?;
+            let model = hex(model, "invalid model")?;
+            ProcessorSignature::from_family_model(family, model)
+                .ok_or(ParseSignatureError { reason: "family and model out of range" })
+        } else {
+            let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+            let eax = hex(digits, "invalid signature")?;
+            Ok(ProcessorSignature { eax, rule: ModelRule::Other })
+        }
+    }
+}
+
+/// An error parsing a `ProcessorSignature`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSignatureError {
+    reason: &'static str,
+}
+
+impl fmt::Display for ParseSignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.reason)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for ParseSignatureError {}
+
+impl Master {
+    /// The processor signature of leaf 1, decoded with the rule of the
+    /// vendor.
+    pub fn processor_signature(&self) -> Option<ProcessorSignature> {
+        self.version_information()
+            .map(|vi| ProcessorSignature::new(vi.registers().eax, &self.vendor()))
+    }
+}
+
+#[test]
+fn signatures() {
+    // Sapphire Rapids
+    let spr = ProcessorSignature::new(0x000806F8, &Vendor::Intel);
+    assert_eq!((spr.family(), spr.model(), spr.stepping()), (0x06, 0x8F, 8));
+    assert_eq!(spr.processor_type(), ProcessorType::Original);
+    assert_eq!(spr.to_string(), "06_8FH");
+    assert_eq!(format!("{:#010X}", spr), "0x000806F8");
+
+    // Zen 3, whose model needs the extended model on AMD
+    let zen3 = ProcessorSignature::new(0x00A20F10, &Vendor::Amd);
+    assert_eq!((zen3.family(), zen3.model()), (0x19, 0x21));
+    assert_eq!(zen3.to_string(), "19_21H");
+
+    // The KX-6000 extends the model in family 7, unlike Intel
+    let kx6000 = ProcessorSignature::new(0x000107B5, &Vendor::Zhaoxin);
+    assert_eq!((kx6000.family(), kx6000.model()), (0x07, 0x1B));
+
+    // A Pentium II OverDrive
+    let overdrive = ProcessorSignature::new(0x00001632, &Vendor::Intel);
+    assert_eq!(overdrive.processor_type(), ProcessorType::OverDrive);
+
+    let parsed: ProcessorSignature = "06_8FH".parse().unwrap();
+    assert_eq!((parsed.family(), parsed.model(), parsed.stepping()), (0x06, 0x8F, 0));
+    let parsed: ProcessorSignature = "19_21".parse().unwrap();
+    assert_eq!((parsed.family(), parsed.model()), (0x19, 0x21));
+    assert_eq!("0x000806F8".parse::<ProcessorSignature>().unwrap().raw(), 0x000806F8);
+    assert_eq!("806f8".parse::<ProcessorSignature>().unwrap().model(), 0x8F);
+
+    // The rule only matters when it changes the model
+    assert_eq!(zen3, ProcessorSignature::new(0x00A20F10, &Vendor::Intel));
+    assert_eq!(zen3, "0x00A20F10".parse().unwrap());
+    let athlon = ProcessorSignature::new(0x00010681, &Vendor::Amd);
+    assert_ne!(athlon, ProcessorSignature::new(0x00010681, &Vendor::Intel));
+
+    assert!("05_1FH".parse::<ProcessorSignature>().is_err());
+    assert!("06_XYH".parse::<ProcessorSignature>().is_err());
+    assert!("0x1000000000".parse::<ProcessorSignature>().is_err());
+    assert!("".parse::<ProcessorSignature>().is_err());
+}
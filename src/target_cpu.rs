@@ -0,0 +1,303 @@
+//! The CPU names accepted by LLVM (`-C target-cpu`) and GCC (`-march`).
+
+use super::{Feature, FeatureSet, Master, Microarchitecture};
+
+// A CPU known to LLVM or GCC, and the features it implies.
+struct Cpu {
+    llvm: Option<&'static str>,
+    gcc: Option<&'static str>,
+    implied: FeatureSet,
+}
+
+impl Cpu {
+    // A CPU with the same name in LLVM and GCC.
+    fn new(name: &'static str, implied: FeatureSet) -> Cpu {
+        Cpu { llvm: Some(name), gcc: Some(name), implied }
+    }
+
+    // A CPU only known to GCC.
+    fn gcc(name: &'static str, implied: FeatureSet) -> Cpu {
+        Cpu { llvm: None, gcc: Some(name), implied }
+    }
+}
+
+fn with(base: FeatureSet, added: &[Feature]) -> FeatureSet {
+    base | added.iter().cloned().collect()
+}
+
+// The named CPU for a microarchitecture.
+//
+// Each CPU implies the features of its predecessor, the way LLVM and
+// GCC define them. Only features detected by this crate are listed, so
+// e.g. XSAVEC and AVX512-BF16 are not checked.
+fn named_cpu(master: &Master, uarch: Microarchitecture, model: u32) -> Cpu {
+    use Feature::*;
+    use Microarchitecture::*;
+    use X86_64Level::*;
+
+    let pentium4 = with(FeatureSet::new(), &[Fpu, Cx8, Cmov, Mmx, Fxsr, Sse, Sse2]);
+    let prescott = with(pentium4, &[Sse3]);
+    let core2 = with(V1.required_features(), &[Sse3, Ssse3, Cmpxchg16b, LahfSahfIn64Bit]);
+    let penryn = with(core2, &[Sse4_1]);
+    let nehalem = V2.required_features();
+    let westmere = with(nehalem, &[Pclmulqdq, Aesni]);
+    let sandybridge = with(westmere, &[Avx, Xsave]);
+    let ivybridge = with(sandybridge, &[F16c, Fsgsbase, Rdrand]);
+    let haswell = ivybridge | V3.required_features();
+    let broadwell = with(haswell, &[Adx, Rdseed, Prefetchw]);
+    let skylake = with(broadwell, &[Clflushopt]);
+    let skylake_avx512 = with(skylake | V4.required_features(), &[Clwb, Pku]);
+    let cascadelake = with(skylake_avx512, &[Avx512vnni]);
+    let cannonlake = with(skylake | V4.required_features(),
+                          &[Pku, Sha, Avx512ifma, Avx512vbmi]);
+    let icelake_client = with(cannonlake, &[
+        Avx512vbmi2, Avx512vnni, Avx512bitalg, Avx512vpopcntdq, Gfni, Vaes, Vpclmulqdq, Rdpid,
+    ]);
+    let icelake_server = with(icelake_client, &[Clwb]);
+    let tigerlake = with(icelake_client, &[Clwb, Avx512Vp2intersect]);
+    let sapphirerapids = with(icelake_server, &[Avx512Fp16]);
+    let alderlake = with(broadwell, &[Clflushopt, Clwb, Sha, Gfni, Vaes, Vpclmulqdq, Rdpid]);
+    let bonnell = with(core2, &[Movbe]);
+    let silvermont = with(nehalem, &[Movbe, Pclmulqdq, Aesni, Rdrand, Prefetchw]);
+    let goldmont = with(silvermont, &[Sha, Rdseed, Xsave, Clflushopt, Fsgsbase]);
+    let goldmont_plus = with(goldmont, &[Rdpid]);
+    let tremont = with(goldmont_plus, &[Clwb, Gfni]);
+    let knl = with(haswell, &[
+        Adx, Rdseed, Prefetchw, Prefetchwt1, Avx512f, Avx512cd, Avx512er, Avx512pf,
+    ]);
+    let knm = with(knl, &[Avx512vpopcntdq, Avx512_4vnniw, Avx512_4fmaps]);
+    let k8_sse3 = with(pentium4, &[Sse3]);
+    let amdfam10 = with(k8_sse3, &[
+        Cmpxchg16b, LahfSahfIn64Bit, Popcnt, Lzcnt, Sse4a, Prefetchw,
+    ]);
+    let btver1 = with(amdfam10 | V1.required_features(), &[Ssse3]);
+    let btver2 = with(btver1 | nehalem, &[Avx, Aesni, Pclmulqdq, Bmi1, F16c, Movbe, Xsave]);
+    let bdver1 = with(amdfam10 | nehalem, &[Avx, Aesni, Pclmulqdq, Xsave]);
+    let bdver2 = with(bdver1, &[F16c, Bmi1, Tbm, Fma]);
+    let bdver3 = with(bdver2, &[Fsgsbase]);
+    let bdver4 = with(bdver3 | V3.required_features(), &[Rdrand]);
+    let znver1 = with(haswell | amdfam10, &[Adx, Rdseed, Sha, Clflushopt]);
+    let znver2 = with(znver1, &[Clwb, Rdpid]);
+    let znver3 = with(znver2, &[Vaes, Vpclmulqdq]);
+    let znver4 = with(znver3 | V4.required_features(), &[
+        Avx512ifma, Avx512vbmi, Avx512vbmi2, Avx512vnni, Avx512bitalg, Avx512vpopcntdq, Gfni,
+    ]);
+    let znver5 = with(znver4, &[Avx512Vp2intersect]);
+    let lujiazui = with(nehalem, &[
+        Movbe, Avx, Aesni, Pclmulqdq, Rdrand, Xsave, Fsgsbase, Lzcnt, Bmi1, Bmi2, F16c, Rdseed,
+    ]);
+    let yongfeng = with(lujiazui | V3.required_features(), &[Sha]);
+
+    match uarch {
+        NetBurst if master.intel_64_bit_architecture() => {
+            Cpu::new("nocona", prescott | V1.required_features())
+        }
+        NetBurst if master.sse3() => Cpu::new("prescott", prescott),
+        NetBurst => Cpu::new("pentium4", pentium4),
+        Core => Cpu::new("core2", core2),
+        Penryn => Cpu { gcc: Some("core2"), ..Cpu::new("penryn", penryn) },
+        Nehalem => Cpu::new("nehalem", nehalem),
+        Westmere => Cpu::new("westmere", westmere),
+        SandyBridge => Cpu::new("sandybridge", sandybridge),
+        IvyBridge => Cpu::new("ivybridge", ivybridge),
+        Haswell => Cpu::new("haswell", haswell),
+        Broadwell => Cpu::new("broadwell", broadwell),
+        Skylake | KabyLake | CoffeeLake | WhiskeyLake | CometLake => {
+            Cpu::new("skylake", skylake)
+        }
+        SkylakeSp => Cpu::new("skylake-avx512", skylake_avx512),
+        CascadeLake => Cpu::new("cascadelake", cascadelake),
+        CooperLake => Cpu::new("cooperlake", cascadelake),
+        CannonLake => Cpu::new("cannonlake", cannonlake),
+        IceLake => Cpu::new("icelake-client", icelake_client),
+        IceLakeSp => Cpu::new("icelake-server", icelake_server),
+        TigerLake => Cpu::new("tigerlake", tigerlake),
+        RocketLake => Cpu::new("rocketlake", icelake_client),
+        AlderLake => Cpu::new("alderlake", alderlake),
+        RaptorLake => Cpu::new("raptorlake", alderlake),
+        MeteorLake => Cpu::new("meteorlake", alderlake),
+        ArrowLake if model == 0xC6 => Cpu::new("arrowlake-s", alderlake),
+        ArrowLake => Cpu::new("arrowlake", alderlake),
+        LunarLake => Cpu::new("lunarlake", alderlake),
+        PantherLake => Cpu::new("pantherlake", alderlake),
+        SapphireRapids => Cpu::new("sapphirerapids", sapphirerapids),
+        EmeraldRapids => Cpu::new("emeraldrapids", sapphirerapids),
+        GraniteRapids if model == 0xAE => Cpu::new("graniterapids-d", sapphirerapids),
+        GraniteRapids => Cpu::new("graniterapids", sapphirerapids),
+        Bonnell | Saltwell => Cpu::new("bonnell", bonnell),
+        Silvermont | Airmont => Cpu::new("silvermont", silvermont),
+        Goldmont => Cpu::new("goldmont", goldmont),
+        GoldmontPlus => Cpu::new("goldmont-plus", goldmont_plus),
+        Tremont => Cpu::new("tremont", tremont),
+        Gracemont => Cpu { gcc: Some("alderlake"), ..Cpu::new("gracemont", alderlake) },
+        SierraForest => Cpu::new("sierraforest", alderlake),
+        GrandRidge => Cpu::new("grandridge", alderlake),
+        ClearwaterForest => Cpu::new("clearwaterforest", alderlake),
+        KnightsLanding => Cpu::new("knl", knl),
+        KnightsMill => Cpu::new("knm", knm),
+        K8 if master.sse3() => Cpu::new("k8-sse3", k8_sse3),
+        K8 => Cpu::new("k8", pentium4),
+        K10 => Cpu::new("amdfam10", amdfam10),
+        Bobcat => Cpu::new("btver1", btver1),
+        Bulldozer => Cpu::new("bdver1", bdver1),
+        Piledriver => Cpu::new("bdver2", bdver2),
+        Steamroller => Cpu::new("bdver3", bdver3),
+        Excavator => Cpu::new("bdver4", bdver4),
+        Jaguar | Puma => Cpu::new("btver2", btver2),
+        Zen | ZenPlus | Dhyana => Cpu::new("znver1", znver1),
+        Zen2 => Cpu::new("znver2", znver2),
+        Zen3 | Zen3Plus => Cpu::new("znver3", znver3),
+        Zen4 => Cpu::new("znver4", znver4),
+        Zen5 => Cpu::new("znver5", znver5),
+        Lujiazui => Cpu::gcc("lujiazui", lujiazui),
+        Yongfeng => Cpu::gcc("yongfeng", yongfeng),
+        Shijidadao => Cpu::gcc("shijidadao", yongfeng),
+    }
+}
+
+impl Master {
+    fn named_cpu(&self) -> Option<Cpu> {
+        let model = self.processor_signature()?.model();
+        self.microarchitecture().map(|uarch| named_cpu(self, uarch, model))
+    }
+
+    // The named CPU, if the processor and OS provide every feature it
+    // implies.
+    fn compatible_cpu(&self) -> Option<Cpu> {
+        let usable = self.usable_features();
+        self.named_cpu().filter(|cpu| cpu.implied.is_subset(&usable))
+    }
+
+    /// The LLVM CPU, as passed to `-C target-cpu`, that best matches
+    /// the processor, such as `skylake-avx512` or `znver3`.
+    ///
+    /// The CPU is chosen from the microarchitecture. When the
+    /// model is unknown, or the processor lacks features the named CPU
+    /// implies (e.g. a Pentium without AVX, or a hypervisor hiding
+    /// AVX-512 or AES), the generic CPU of the highest satisfied x86-64
+    /// level is returned instead.
+    pub fn llvm_target_cpu(&self) -> &'static str {
+        self.compatible_cpu()
+            .and_then(|cpu| cpu.llvm)
+            .unwrap_or_else(|| generic_cpu(self))
+    }
+
+    /// The GCC (and Clang) `-march` value that best matches the
+    /// processor, such as `haswell`, `znver2` or `tremont`, the way
+    /// `-march=native` would pick it.
+    ///
+    /// Like `llvm_target_cpu`, this falls back to the safest compatible
+    /// choice, the generic CPU of the highest satisfied x86-64 level,
+    /// when the model is unknown or lacks features of the named CPU.
+    pub fn gcc_march(&self) -> &'static str {
+        self.compatible_cpu()
+            .and_then(|cpu| cpu.gcc)
+            .unwrap_or_else(|| generic_cpu(self))
+    }
+
+    /// The GCC `-mtune` value for the processor.
+    ///
+    /// Tuning does not affect which instructions are used, so the named
+    /// CPU is returned even if features are missing; unknown models are
+    /// tuned for as `generic`.
+    pub fn gcc_mtune(&self) -> &'static str {
+        self.named_cpu().and_then(|cpu| cpu.gcc).unwrap_or("generic")
+    }
+}
+
+// The most capable CPU that only assumes the level of the processor.
+fn generic_cpu(master: &Master) -> &'static str {
+    match master.x86_64_level() {
+        Some(level) => level.name(),
+        None if master.cmov() => "i686",
+        None => "i386",
+    }
+}
+
+#[test]
+fn target_cpus() {
+    use super::{CpuidResult, Register};
+
+    fn with_signature(vendor: &'static [u8; 12], eax: u32, added: &'static [Feature]) -> Master {
+        let source = move |leaf, subleaf| {
+            let mut registers = ::haswell_source(leaf, subleaf);
+            match leaf {
+                0 => {
+                    let word = |i: usize| {
+                        u32::from_le_bytes([vendor[i], vendor[i + 1], vendor[i + 2], vendor[i + 3]])
+                    };
+                    registers = CpuidResult { eax: 0xD, ebx: word(0), edx: word(4), ecx: word(8) };
+                }
+                1 => registers.eax = eax,
+                _ => {}
+            }
+            for f in added.iter().filter(|f| f.leaf() == leaf && f.subleaf() == subleaf) {
+                let register = match f.register() {
+                    Register::Eax => &mut registers.eax,
+                    Register::Ebx => &mut registers.ebx,
+                    Register::Ecx => &mut registers.ecx,
+                    Register::Edx => &mut registers.edx,
+                };
+                *register |= 1 << f.bit();
+            }
+            registers
+        };
+        Master::from_source(&source)
+    }
+
+    const ZEN3: &[Feature] = &[
+        Feature::Sse4a, Feature::Prefetchw, Feature::Adx, Feature::Rdseed, Feature::Sha,
+        Feature::Clflushopt, Feature::Clwb, Feature::Rdpid, Feature::Vaes, Feature::Vpclmulqdq,
+    ];
+
+    assert_eq!(Master::from_source(&::haswell_source).llvm_target_cpu(), "haswell");
+
+    // Zen 3 (family 0x19, model 0x21)
+    assert_eq!(with_signature(b"AuthenticAMD", 0x00A20F10, ZEN3).llvm_target_cpu(), "znver3");
+
+    // A hypervisor hiding SHA leaves an x86-64-v3 processor, not a Zen 3
+    let masked = &[
+        Feature::Sse4a, Feature::Prefetchw, Feature::Adx, Feature::Rdseed,
+        Feature::Clflushopt, Feature::Clwb, Feature::Rdpid, Feature::Vaes, Feature::Vpclmulqdq,
+    ];
+    assert_eq!(with_signature(b"AuthenticAMD", 0x00A20F10, masked).llvm_target_cpu(), "x86-64-v3");
+
+    // Zen 4 needs AVX-512
+    assert_eq!(with_signature(b"AuthenticAMD", 0x00A60F12, ZEN3).llvm_target_cpu(), "x86-64-v3");
+
+    // A Haswell without AES is no longer a haswell
+    let no_aes = |leaf, subleaf| {
+        let mut registers = ::haswell_source(leaf, subleaf);
+        if leaf == 1 {
+            registers.ecx &= !(1 << 25);
+        }
+        registers
+    };
+    let no_aes = Master::from_source(&no_aes);
+    assert_eq!(no_aes.x86_64_level(), Some(::X86_64Level::V3));
+    assert_eq!(no_aes.llvm_target_cpu(), "x86-64-v3");
+    assert_eq!(no_aes.gcc_march(), "x86-64-v3");
+    assert_eq!(no_aes.gcc_mtune(), "haswell");
+
+    // An unknown future model
+    let unknown = with_signature(b"GenuineIntel", 0x000F06F0, &[]);
+    assert_eq!(unknown.llvm_target_cpu(), "x86-64-v3");
+    assert_eq!(unknown.gcc_march(), "x86-64-v3");
+    assert_eq!(unknown.gcc_mtune(), "generic");
+
+    // Penryn is known to GCC as a Core 2
+    let penryn = with_signature(b"GenuineIntel", 0x0001067A, &[]);
+    assert_eq!(penryn.llvm_target_cpu(), "penryn");
+    assert_eq!(penryn.gcc_march(), "core2");
+
+    // LLVM does not know Zhaoxin processors
+    let kx6000 = with_signature(b"  Shanghai  ", 0x000107B5, &[Feature::Rdseed]);
+    assert_eq!(kx6000.llvm_target_cpu(), "x86-64-v3");
+    assert_eq!(kx6000.gcc_march(), "lujiazui");
+
+    // A Zen 4 without AVX-512 is only safe as x86-64-v3, but is still
+    // tuned for as a Zen 4
+    let zen4 = with_signature(b"AuthenticAMD", 0x00A60F12, ZEN3);
+    assert_eq!(zen4.gcc_march(), "x86-64-v3");
+    assert_eq!(zen4.gcc_mtune(), "znver4");
+}
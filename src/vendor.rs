@@ -0,0 +1,99 @@
+//! The manufacturer identification of leaf 0.
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::write_signature;
+
+/// The processor manufacturer, as identified by the vendor string.
+///
+/// Emulators and translation layers such as Rosetta 2 report their own
+/// vendor string instead of the one of the underlying processor; those
+/// are listed separately. Hypervisors pass the vendor string through and
+/// identify themselves in leaf 0x40000000 instead; see `Hypervisor`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Vendor {
+    Intel,
+    Amd,
+    Hygon,
+    Zhaoxin,
+    Centaur,
+    Via,
+    Cyrix,
+    Transmeta,
+    NexGen,
+    Rise,
+    Sis,
+    Umc,
+    NationalSemi,
+    /// Microsoft's x86 emulator on ARM
+    MicrosoftXta,
+    /// Apple's Rosetta 2 translation layer
+    VirtualApple,
+    /// A vendor string not known to this crate
+    Unknown([u8; 12]),
+}
+
+impl Vendor {
+    /// Identifies the vendor from the 12 bytes of EBX, EDX and ECX of leaf 0.
+    pub fn from_vendor_string(bytes: &[u8; 12]) -> Vendor {
+        match bytes {
+            b"GenuineIntel" | b"GenuineIotel" => Vendor::Intel,
+            b"AuthenticAMD" | b"AMDisbetter!" => Vendor::Amd,
+            b"HygonGenuine" => Vendor::Hygon,
+            b"  Shanghai  " => Vendor::Zhaoxin,
+            b"CentaurHauls" => Vendor::Centaur,
+            b"VIA VIA VIA " => Vendor::Via,
+            b"CyrixInstead" => Vendor::Cyrix,
+            b"GenuineTMx86" | b"TransmetaCPU" => Vendor::Transmeta,
+            b"NexGenDriven" => Vendor::NexGen,
+            b"RiseRiseRise" => Vendor::Rise,
+            b"SiS SiS SiS " => Vendor::Sis,
+            b"UMC UMC UMC " => Vendor::Umc,
+            b"Geode by NSC" => Vendor::NationalSemi,
+            b"MicrosoftXTA" => Vendor::MicrosoftXta,
+            b"VirtualApple" => Vendor::VirtualApple,
+            _ => Vendor::Unknown(*bytes),
+        }
+    }
+}
+
+impl fmt::Display for Vendor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Vendor::Intel => "Intel",
+            Vendor::Amd => "AMD",
+            Vendor::Hygon => "Hygon",
+            Vendor::Zhaoxin => "Zhaoxin",
+            Vendor::Centaur => "Centaur",
+            Vendor::Via => "VIA",
+            Vendor::Cyrix => "Cyrix",
+            Vendor::Transmeta => "Transmeta",
+            Vendor::NexGen => "NexGen",
+            Vendor::Rise => "Rise",
+            Vendor::Sis => "SiS",
+            Vendor::Umc => "UMC",
+            Vendor::NationalSemi => "National Semiconductor",
+            Vendor::MicrosoftXta => "Microsoft x86-to-ARM",
+            Vendor::VirtualApple => "Apple Rosetta 2",
+            Vendor::Unknown(ref bytes) => return write_signature(f, bytes),
+        };
+        f.write_str(name)
+    }
+}
+
+#[test]
+fn known_and_unknown_vendors() {
+    assert_eq!(Vendor::from_vendor_string(b"GenuineIntel"), Vendor::Intel);
+    assert_eq!(Vendor::from_vendor_string(b"AuthenticAMD"), Vendor::Amd);
+    assert_eq!(Vendor::from_vendor_string(b"  Shanghai  "), Vendor::Zhaoxin);
+    assert_eq!(Vendor::from_vendor_string(b"VirtualApple"), Vendor::VirtualApple);
+    assert_eq!(Vendor::from_vendor_string(b"KVMKVMKVM\0\0\0"),
+               Vendor::Unknown(*b"KVMKVMKVM\0\0\0"));
+    assert_eq!(Vendor::from_vendor_string(b"Vortex86 SoC"), Vendor::Unknown(*b"Vortex86 SoC"));
+    assert_eq!(Vendor::Unknown(*b"Vortex86 SoC").to_string(), "Vortex86 SoC");
+    assert_eq!(Vendor::Unknown(*b"Vortex\xFF\xFEXYZW").to_string(), "Vortex\u{FFFD}\u{FFFD}XYZW");
+}